@@ -0,0 +1,141 @@
+use std::rc::Rc;
+
+use crate::error::TreeError;
+use crate::{Aggregate, RedBlackTreeStructure, Tree};
+
+// Children are strong `Rc`s and parents are `Weak`s, so a slip in a rotation or in `delete_node`
+// does not crash: it silently orphans a subtree (still alive if anything holds it) or leaves a
+// parent link pointing at the wrong node. The audit catches both.
+
+impl<T, C, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Turns the audit on or off. While it is on the tree remembers a weak handle to every node it
+    /// creates, and in debug builds runs `audit` after each insert and remove; meant for test
+    /// suites. The first failure panics, or comes back as the error of a `try_*` method.
+    pub fn set_audit(&mut self, enabled: bool) {
+        self.tracked = None;
+        if enabled {
+            let mut tracked = Vec::with_capacity(self.len);
+            let mut stack: Vec<Tree<T, A::Value>> = self.root.iter().cloned().collect();
+            while let Some(node) = stack.pop() {
+                tracked.push(Rc::downgrade(&node));
+                stack.extend(node.borrow().left.clone());
+                stack.extend(node.borrow().right.clone());
+            }
+            self.tracked = Some(tracked);
+        }
+    }
+
+    /// Checks that every node created since `set_audit(true)` that is still alive is reachable
+    /// from the root, that exactly `len()` nodes are, and that each child's parent link upgrades to
+    /// the node that owns it. The live-node count is only checked while auditing is on.
+    pub fn audit(&self) -> Result<(), TreeError> {
+        let mut reachable = 0;
+        let mut stack: Vec<Tree<T, A::Value>> = Vec::new();
+        if let Some(root) = &self.root {
+            if root.borrow().parent.is_some() {
+                return Err(TreeError::InvariantViolated("root has a parent link".to_string()));
+            }
+            stack.push(root.clone());
+        }
+        while let Some(node) = stack.pop() {
+            reachable += 1;
+            let (left, right) = (node.borrow().left.clone(), node.borrow().right.clone());
+            for child in [left, right].into_iter().flatten() {
+                let parent = child.borrow().parent.as_ref().and_then(|parent| parent.upgrade());
+                if !parent.is_some_and(|parent| Rc::ptr_eq(&parent, &node)) {
+                    return Err(TreeError::InvariantViolated("child's parent link does not point at the node that owns it".to_string()));
+                }
+                stack.push(child);
+            }
+        }
+        if reachable != self.len {
+            return Err(TreeError::InvariantViolated(format!("len is {} but {} nodes are reachable", self.len, reachable)));
+        }
+        if let Some(tracked) = &self.tracked {
+            let live = tracked.iter().filter(|node| node.strong_count() > 0).count();
+            if live != reachable {
+                return Err(TreeError::InvariantViolated(format!(
+                    "{} nodes are alive but only {} are reachable from the root",
+                    live, reachable
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remembers a newly created node while auditing is on, dropping handles to freed nodes now
+    /// and then so the list stays proportional to the tree.
+    pub(crate) fn track(&mut self, node: &Tree<T, A::Value>) {
+        let len = self.len;
+        if let Some(tracked) = &mut self.tracked {
+            if tracked.len() > 2 * len + 16 {
+                tracked.retain(|node| node.strong_count() > 0);
+            }
+            tracked.push(Rc::downgrade(node));
+        }
+    }
+
+    pub(crate) fn audit_if_enabled(&self) -> Result<(), TreeError> {
+        if cfg!(debug_assertions) && self.tracked.is_some() {
+            return self.audit();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::mem;
+
+    use crate::bench::workload;
+    use crate::RedBlackTreeStructure;
+
+    const DEPTH: usize = 50;
+
+    #[test]
+    fn random_edits_pass_validate_and_audit_after_every_step() {
+        let mut tree: RedBlackTreeStructure<u64> = RedBlackTreeStructure::new();
+        tree.set_audit(true);
+        tree.enable_history(DEPTH);
+        let mut model = BTreeSet::new();
+        // The model's earlier and undone states, mirroring what the journal holds.
+        let (mut undo, mut redo) = (Vec::new(), Vec::new());
+        for step in workload(3000, 42) {
+            let key = step % 400;
+            let before = model.clone();
+            match (step >> 20) % 10 {
+                0 => {
+                    assert_eq!(tree.undo(), !undo.is_empty());
+                    if let Some(previous) = undo.pop() {
+                        redo.push(mem::replace(&mut model, previous));
+                    }
+                }
+                1 => {
+                    assert_eq!(tree.redo(), !redo.is_empty());
+                    if let Some(next) = redo.pop() {
+                        undo.push(mem::replace(&mut model, next));
+                    }
+                }
+                2 => {
+                    let divisor = 2 + (step >> 30) % 5;
+                    tree.retain(|key| key % divisor != 0);
+                    model.retain(|key| key % divisor != 0);
+                }
+                3..=5 => assert_eq!(tree.remove(&key).is_some(), model.remove(&key)),
+                _ => assert_eq!(tree.insert(key), model.insert(key)),
+            }
+            // Only changes are journaled, and each one clears what could be redone.
+            if (step >> 20) % 10 >= 2 && model != before {
+                undo.push(before);
+                if undo.len() > DEPTH {
+                    undo.remove(0);
+                }
+                redo.clear();
+            }
+            assert!(tree.validate().is_ok());
+            assert!(tree.audit().is_ok());
+            assert!(tree.inorder().eq(model.iter().copied()));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::llrb::LeftLeaningRedBlackTree;
+use crate::RedBlackTreeStructure;
+
+/// What one tree variant did on the shared workload.
+#[derive(Debug)]
+pub struct VariantReport {
+    pub name: &'static str,
+    pub inserts: usize,
+    pub removes: usize,
+    pub insert_time: Duration,
+    pub remove_time: Duration,
+    pub rotations: u64,
+    pub height: u32,
+    pub valid: bool,
+    /// Lines of code on the insert and remove paths, see `INSERT_PATHS` and `REMOVE_PATHS`;
+    /// `None` if one of the functions listed there no longer exists.
+    pub insert_lines: Option<usize>,
+    pub remove_lines: Option<usize>,
+}
+
+/// The functions an insert runs through in each variant, helpers included. Shared helpers such
+/// as the rotations count towards both paths.
+const INSERT_PATHS: [&[&str]; 2] = [
+    &["insert_key", "search", "attach", "fix_insert", "rotate_left", "rotate_right", "recolor"],
+    &["insert", "insert_below", "balance", "rotate_left", "rotate_right", "flip_colors", "blacken_root"],
+];
+
+const REMOVE_PATHS: [&[&str]; 2] = [
+    &["try_remove", "take_node", "delete_node", "transplant", "find_mininmum_node", "fix_delete", "rotate_left", "rotate_right", "recolor"],
+    &[
+        "remove", "remove_below", "remove_min", "move_red_left", "move_red_right", "balance", "rotate_left", "rotate_right",
+        "flip_colors", "redden_root", "blacken_root",
+    ],
+];
+
+/// Counts the lines of the functions called `names` in `source`, leaving out blank lines and
+/// comments. A function runs from its `fn` line to the `}` at the same indentation. Returns `None`
+/// if one of the names is not declared in `source`.
+fn code_lines(source: &str, names: &[&str]) -> Option<usize> {
+    let lines: Vec<&str> = source.lines().collect();
+    names
+        .iter()
+        .map(|name| {
+            let start = lines
+                .iter()
+                .position(|line| {
+                    let line = line.trim_start();
+                    let declares = ["fn ", "pub fn ", "pub(crate) fn "].iter().any(|prefix| line.starts_with(prefix));
+                    declares && (line.contains(&format!("fn {}(", name)) || line.contains(&format!("fn {}<", name)))
+                })?;
+            let indent = &lines[start][..lines[start].len() - lines[start].trim_start().len()];
+            let close = format!("{}}}", indent);
+            let len = lines[start..].iter().position(|line| *line == close).map_or(1, |end| end + 1);
+            Some(lines[start..start + len].iter().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with("//")).count())
+        })
+        .sum()
+}
+
+/// Up to `n` distinct pseudo-random keys from a xorshift generator, the same for every run with
+/// `seed`.
+pub(crate) fn workload(n: usize, seed: u64) -> Vec<u64> {
+    let mut state = seed.max(1);
+    let mut seen = HashSet::with_capacity(n);
+    let mut keys = Vec::with_capacity(n);
+    for _ in 0..n {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        if seen.insert(state) {
+            keys.push(state);
+        }
+    }
+    keys
+}
+
+/// Inserts `n` keys into each variant, then removes every other one, timing both phases and
+/// counting the rotations they needed.
+pub fn compare_variants(n: usize, seed: u64) -> Vec<VariantReport> {
+    let keys = workload(n, seed);
+    let removed: Vec<u64> = keys.iter().copied().step_by(2).collect();
+
+    let mut tree = RedBlackTreeStructure::new();
+    let start = Instant::now();
+    for &key in &keys {
+        tree.insert(key);
+    }
+    let insert_time = start.elapsed();
+    let start = Instant::now();
+    for &key in &removed {
+        tree.delete(key);
+    }
+    let remove_time = start.elapsed();
+    let classic = VariantReport {
+        name: "classic",
+        inserts: keys.len(),
+        removes: removed.len(),
+        insert_time,
+        remove_time,
+        rotations: tree.rotations(),
+        height: RedBlackTreeStructure::<u64>::height_of_tree(&tree.root),
+        valid: tree.validate().is_ok(),
+        insert_lines: code_lines(include_str!("main.rs"), INSERT_PATHS[0]),
+        remove_lines: code_lines(include_str!("main.rs"), REMOVE_PATHS[0]),
+    };
+
+    let mut tree = LeftLeaningRedBlackTree::new();
+    let start = Instant::now();
+    for &key in &keys {
+        tree.insert(key);
+    }
+    let insert_time = start.elapsed();
+    let start = Instant::now();
+    for key in &removed {
+        tree.remove(key);
+    }
+    let remove_time = start.elapsed();
+    let left_leaning = VariantReport {
+        name: "left-leaning",
+        inserts: keys.len(),
+        removes: removed.len(),
+        insert_time,
+        remove_time,
+        rotations: tree.rotations(),
+        height: tree.height_of_tree(),
+        valid: tree.validate().is_ok(),
+        insert_lines: code_lines(include_str!("llrb.rs"), INSERT_PATHS[1]),
+        remove_lines: code_lines(include_str!("llrb.rs"), REMOVE_PATHS[1]),
+    };
+
+    vec![classic, left_leaning]
+}
+
+pub fn print_comparison(reports: &[VariantReport]) {
+    println!(
+        "{:<14}{:>10}{:>10}{:>14}{:>14}{:>12}{:>8}{:>7}{:>13}{:>13}",
+        "variant", "inserts", "removes", "insert ms", "remove ms", "rotations", "height", "valid", "insert lines", "remove lines"
+    );
+    let lines = |lines: Option<usize>| lines.map_or_else(|| "?".to_string(), |lines| lines.to_string());
+    for report in reports {
+        println!(
+            "{:<14}{:>10}{:>10}{:>14.3}{:>14.3}{:>12}{:>8}{:>7}{:>13}{:>13}",
+            report.name,
+            report.inserts,
+            report.removes,
+            report.insert_time.as_secs_f64() * 1000.0,
+            report.remove_time.as_secs_f64() * 1000.0,
+            report.rotations,
+            report.height,
+            report.valid,
+            lines(report.insert_lines),
+            lines(report.remove_lines),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_lines, compare_variants};
+
+    #[test]
+    fn code_lines_skips_blanks_and_comments() {
+        let source = "fn a() {\n    // note\n\n    let x = 1;\n}\n\n    pub fn b<T>(t: T) {\n        {\n        }\n    }\n";
+        assert_eq!(code_lines(source, &["a"]), Some(3));
+        assert_eq!(code_lines(source, &["a", "b"]), Some(7));
+        assert_eq!(code_lines(source, &["a", "c"]), None);
+    }
+
+    #[test]
+    fn both_variants_stay_valid_and_report_their_code_size() {
+        // Renaming or reworking a function on either path changes these counts.
+        let expected = [("classic", 170, 213), ("left-leaning", 67, 124)];
+        for (report, (name, insert_lines, remove_lines)) in compare_variants(500, 7).into_iter().zip(expected) {
+            assert_eq!(report.name, name);
+            assert!(report.valid, "{}", name);
+            assert_eq!(report.inserts, 500);
+            assert_eq!((report.insert_lines, report.remove_lines), (Some(insert_lines), Some(remove_lines)), "{}", name);
+        }
+    }
+}
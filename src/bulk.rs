@@ -0,0 +1,161 @@
+use std::rc::Rc;
+
+use crate::error::intact;
+use crate::{Aggregate, Compare, NodeColor, RedBlackTree, RedBlackTreeStructure, Tree, TreeNode};
+
+#[allow(dead_code, reason = "the demo deletes keys one at a time")]
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Keeps only the keys for which `keep` returns `true`, visiting them in ascending order.
+    /// A node is removed as a whole, whatever its count under `DuplicatePolicy::Count`.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) {
+        self.remove_where(|key| !keep(key));
+    }
+
+    /// Removes the keys for which `extract` returns `true`, visiting them in ascending order, and
+    /// returns them in that order.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, extract: F) -> Vec<T> {
+        self.remove_where(extract)
+    }
+
+    /// Tests every key once, then either deletes the matching nodes one by one or, when that
+    /// would cost more than the O(n) pass already made, takes the whole tree apart and builds a
+    /// balanced one from the keys that stay.
+    fn remove_where<F: FnMut(&T) -> bool>(&mut self, mut remove: F) -> Vec<T> {
+        let mut matched = Vec::with_capacity(self.len);
+        let mut doomed = Vec::new();
+        let mut stack = Vec::new();
+        let mut current = self.root.clone();
+        while current.is_some() || !stack.is_empty() {
+            while let Some(node) = current {
+                current = node.borrow().left.clone();
+                stack.push(node);
+            }
+            let node = stack.pop().expect("loop condition: stack is not empty");
+            current = node.borrow().right.clone();
+            let hit = remove(node.borrow().key());
+            matched.push(hit);
+            if hit {
+                doomed.push(node);
+            }
+        }
+
+        if doomed.is_empty() {
+            return Vec::new();
+        }
+        self.record();
+
+        // A delete costs O(log n), so past n / log2(n) of them rebuilding is cheaper.
+        let log_len = (usize::BITS - self.len.leading_zeros()) as usize;
+        if doomed.len() * log_len < self.len {
+            return doomed.into_iter().map(|node| intact(self.take_node(node))).collect();
+        }
+
+        drop(doomed);
+        let mut kept = Vec::with_capacity(self.len);
+        let mut removed = Vec::new();
+        for ((key, count), hit) in self.take_all().into_iter().zip(matched) {
+            if hit {
+                removed.push(key);
+            } else {
+                kept.push((key, count));
+            }
+        }
+        self.rebuild_from_sorted(kept);
+        removed
+    }
+
+    /// Empties the tree and returns its keys with their counts in ascending order.
+    pub(crate) fn take_all(&mut self) -> Vec<(T, usize)> {
+        let mut keys = Vec::with_capacity(self.len);
+        let mut stack = Vec::new();
+        let mut current = self.root.take();
+        while current.is_some() || !stack.is_empty() {
+            while let Some(node) = current {
+                current = node.borrow_mut().left.take();
+                stack.push(node);
+            }
+            let node = stack.pop().expect("loop condition: stack is not empty");
+            current = node.borrow_mut().right.take();
+            let mut node = node.borrow_mut();
+            keys.push((node.key.take().expect("every node in the tree holds a key"), node.count));
+        }
+        self.len = 0;
+        keys
+    }
+
+    /// Replaces the contents with `keys`, which must be strictly ascending under the tree's
+    /// comparator, as a balanced tree built in O(n).
+    ///
+    /// Each subtree takes the middle key as its root, so every level but the deepest is full;
+    /// colouring the nodes on that last level red (unless it is the root) keeps the black height
+    /// the same along every path.
+    pub(crate) fn rebuild_from_sorted(&mut self, keys: Vec<(T, usize)>) {
+        let len = keys.len();
+        let deepest = len.checked_ilog2().unwrap_or(0) as usize;
+        let mut keys = keys.into_iter();
+        self.root = self.build_balanced(&mut keys, len, 0, deepest);
+        self.len = len;
+        if self.tracked.is_some() {
+            self.set_audit(true);
+        }
+        intact(self.audit_if_enabled());
+    }
+
+    fn build_balanced(&self, keys: &mut impl Iterator<Item = (T, usize)>, size: usize, depth: usize, deepest: usize) -> RedBlackTree<T, A::Value> {
+        if size == 0 {
+            return None;
+        }
+        let left_size = (size - 1) / 2;
+        let left = self.build_balanced(keys, left_size, depth + 1, deepest);
+        let (key, count) = keys.next().expect("size matches the number of keys");
+        let right = self.build_balanced(keys, size - 1 - left_size, depth + 1, deepest);
+
+        let node: Tree<T, A::Value> = TreeNode::new(key, self.aggregate.identity());
+        {
+            let mut inner = node.borrow_mut();
+            inner.count = count;
+            if depth == deepest && depth > 0 {
+                inner.color = NodeColor::Red;
+            } else {
+                inner.color = NodeColor::Black;
+            }
+            for child in [&left, &right].into_iter().flatten() {
+                child.borrow_mut().parent = Some(Rc::downgrade(&node));
+            }
+            inner.left = left;
+            inner.right = right;
+        }
+        self.refresh(&node);
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DuplicatePolicy, RedBlackTreeStructure};
+
+    #[test]
+    fn few_and_many_removals_leave_a_valid_tree() {
+        // Removing 3 of 100 keys deletes them one by one; removing half rebuilds the tree.
+        for divisor in [40, 2] {
+            let mut tree: RedBlackTreeStructure<i32> = (0..100).collect();
+            tree.set_audit(true);
+            let extracted = tree.extract_if(|key| key % divisor == 0);
+            assert_eq!(extracted, (0..100).step_by(divisor as usize).collect::<Vec<_>>());
+            assert_eq!(tree.len(), 100 - extracted.len());
+            assert!(tree.validate().is_ok() && tree.audit().is_ok());
+        }
+    }
+
+    #[test]
+    fn retain_drops_whole_counted_nodes() {
+        let mut tree = RedBlackTreeStructure::with_policy(DuplicatePolicy::Count);
+        for key in [1, 1, 2, 3, 3, 3] {
+            tree.insert(key);
+        }
+        tree.retain(|key| *key != 3);
+        assert_eq!((tree.count(&1), tree.count(&3), tree.len()), (2, 0, 2));
+        tree.retain(|_| true);
+        assert_eq!(tree.len(), 2);
+    }
+}
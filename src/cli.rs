@@ -0,0 +1,429 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::error::TreeError;
+use crate::export::{Dot, GraphMl, Mermaid};
+use crate::{bench, diff, metrics, Dotfile, RedBlackTreeStructure};
+
+const USAGE: &str = "\
+Usage: Project2 [draw] [KEY...] [OPTIONS]
+       Project2 bench [--size N] [--seed N]
+       Project2 metrics [--size N] [--seed N] [--every N] [--out-dir DIR]
+       Project2 help
+
+draw (the default) inserts the keys, draws the tree, deletes the keys to delete, and draws the
+tree again along with a diff of the two. Without any keys it runs the original demo.
+
+Options of draw:
+  --keys LIST        keys to insert, separated by commas or whitespace
+  --keys-file PATH   read keys to insert from a file
+  --stdin            read keys to insert from standard input
+  --delete LIST      keys to delete after drawing (may be repeated)
+  --out-dir DIR      where to write rbt, rbt_after_delete and rbt_diff (default: .)
+  --format FORMAT    dot, mermaid or graphml (default: dot)
+  --dump, --no-dump  whether to print the debug dump of the nodes (default: --dump)
+  --validate         check the red-black properties and parent links after each phase
+
+Options of bench and metrics:
+  --size N           keys in the pseudo-random workload (default: 100000)
+  --seed N           seed of the workload
+  --every N          metrics: sample every N operations (default: 1000)
+  --out-dir DIR      metrics: where to write metrics.csv (default: .)
+
+Exit codes: 0 on success, 1 if writing a file or validating the tree failed, 2 if the command
+line or the keys are malformed.
+";
+
+/// Options that take no value, and options that do, of any subcommand.
+const SWITCHES: [&str; 4] = ["--stdin", "--dump", "--no-dump", "--validate"];
+const VALUED: [&str; 8] = ["--keys", "--keys-file", "--delete", "--out-dir", "--format", "--size", "--seed", "--every"];
+
+const DEMO_KEYS: [i64; 9] = [10, 20, 30, 15, 25, 5, 6, 1, 43];
+const DEMO_DELETES: [i64; 4] = [5, 30, 10, 43];
+const DEFAULT_SIZE: usize = 100_000;
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Why the binary exits unsuccessfully.
+enum CliError {
+    /// The command line or the keys given are malformed; exit code 2.
+    Usage(String),
+    /// Reading keys, writing a file or validating the tree failed; exit code 1.
+    Tree(TreeError),
+}
+
+impl From<TreeError> for CliError {
+    fn from(err: TreeError) -> Self {
+        CliError::Tree(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Tree(TreeError::Io(err))
+    }
+}
+
+/// The format `draw` writes its drawings in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Dot,
+    Mermaid,
+    GraphMl,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Dot => "dot",
+            Format::Mermaid => "mmd",
+            Format::GraphMl => "graphml",
+        }
+    }
+
+    fn write(self, file: &Dotfile) -> Result<(), TreeError> {
+        match self {
+            Format::Dot => file.write_as(&Dot),
+            Format::Mermaid => file.write_as(&Mermaid),
+            Format::GraphMl => file.write_as(&GraphMl),
+        }
+    }
+}
+
+/// Where `draw` takes keys to insert from, read in the order given.
+enum KeySource {
+    List(String),
+    File(String),
+    Stdin,
+}
+
+struct DrawOptions {
+    keys: Vec<KeySource>,
+    deletes: Option<Vec<i64>>,
+    out_dir: String,
+    format: Format,
+    dump: bool,
+    validate: bool,
+}
+
+enum Command {
+    Draw(DrawOptions),
+    Bench { size: usize, seed: u64 },
+    Metrics { size: usize, seed: u64, every: usize, out_dir: String },
+    Help,
+}
+
+/// Runs the command line `args`, program name excluded.
+pub fn run<I: IntoIterator<Item = String>>(args: I) -> ExitCode {
+    let result = parse(args.into_iter().collect()).and_then(|command| match command {
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Command::Bench { size, seed } => {
+            bench::print_comparison(&bench::compare_variants(size, seed));
+            Ok(())
+        }
+        Command::Metrics { size, seed, every, out_dir } => run_metrics(size, seed, every, &out_dir),
+        Command::Draw(options) => run_draw(options),
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            ExitCode::from(2)
+        }
+        Err(CliError::Tree(err)) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse(args: Vec<String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter().peekable();
+    let subcommand = match args.peek().map(String::as_str) {
+        Some("draw" | "bench" | "metrics" | "help") => args.next(),
+        _ => None,
+    };
+
+    let mut positional = Vec::new();
+    let mut flags = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+        // `--flag=value` and `--flag value` are both accepted; switches take no value.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let value = if SWITCHES.contains(&flag.as_str()) {
+            if inline.is_some() {
+                return Err(CliError::Usage(format!("{} does not take a value", flag)));
+            }
+            None
+        } else if VALUED.contains(&flag.as_str()) {
+            match inline.or_else(|| args.next()) {
+                Some(value) => Some(value),
+                None => return Err(CliError::Usage(format!("{} needs a value", flag))),
+            }
+        } else {
+            return Err(CliError::Usage(format!("unknown option {}", flag)));
+        };
+        flags.push((flag, value));
+    }
+
+    match subcommand.as_deref() {
+        Some("help") => Ok(Command::Help),
+        Some("bench") => {
+            no_positional(&positional)?;
+            let (mut size, mut seed) = (DEFAULT_SIZE, DEFAULT_SEED);
+            for (flag, value) in flags {
+                let value = value.unwrap_or_default();
+                match flag.as_str() {
+                    "--size" => size = number(&flag, &value)?,
+                    "--seed" => seed = number(&flag, &value)?,
+                    _ => return Err(unknown(&flag, "bench")),
+                }
+            }
+            Ok(Command::Bench { size, seed })
+        }
+        Some("metrics") => {
+            no_positional(&positional)?;
+            let (mut size, mut seed, mut every, mut out_dir) = (DEFAULT_SIZE, DEFAULT_SEED, 1_000, ".".to_string());
+            for (flag, value) in flags {
+                let value = value.unwrap_or_default();
+                match flag.as_str() {
+                    "--size" => size = number(&flag, &value)?,
+                    "--seed" => seed = number(&flag, &value)?,
+                    "--every" => every = number(&flag, &value)?,
+                    "--out-dir" => out_dir = value,
+                    _ => return Err(unknown(&flag, "metrics")),
+                }
+            }
+            Ok(Command::Metrics { size, seed, every, out_dir })
+        }
+        _ => {
+            let mut options = DrawOptions { keys: Vec::new(), deletes: None, out_dir: ".".to_string(), format: Format::Dot, dump: true, validate: false };
+            if !positional.is_empty() {
+                options.keys.push(KeySource::List(positional.join(" ")));
+            }
+            for (flag, value) in flags {
+                let value = value.unwrap_or_default();
+                match flag.as_str() {
+                    "--keys" => options.keys.push(KeySource::List(value)),
+                    "--keys-file" => options.keys.push(KeySource::File(value)),
+                    "--stdin" => options.keys.push(KeySource::Stdin),
+                    "--delete" => options.deletes.get_or_insert_with(Vec::new).extend(parse_keys(&value, "--delete")?),
+                    "--out-dir" => options.out_dir = value,
+                    "--format" => {
+                        options.format = match value.as_str() {
+                            "dot" => Format::Dot,
+                            "mermaid" => Format::Mermaid,
+                            "graphml" => Format::GraphMl,
+                            other => return Err(CliError::Usage(format!("unknown format `{}`, expected dot, mermaid or graphml", other))),
+                        }
+                    }
+                    "--dump" => options.dump = true,
+                    "--no-dump" => options.dump = false,
+                    "--validate" => options.validate = true,
+                    _ => return Err(unknown(&flag, "draw")),
+                }
+            }
+            Ok(Command::Draw(options))
+        }
+    }
+}
+
+fn no_positional(positional: &[String]) -> Result<(), CliError> {
+    match positional.first() {
+        Some(arg) => Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        None => Ok(()),
+    }
+}
+
+fn unknown(flag: &str, subcommand: &str) -> CliError {
+    CliError::Usage(format!("unknown option {} for {}", flag, subcommand))
+}
+
+fn number<N: std::str::FromStr>(flag: &str, value: &str) -> Result<N, CliError> {
+    value.parse().map_err(|_| CliError::Usage(format!("{} expects a non-negative integer, got `{}`", flag, value)))
+}
+
+/// Integers separated by commas and/or whitespace; `origin` names where they came from.
+fn parse_keys(text: &str, origin: &str) -> Result<Vec<i64>, CliError> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|key| !key.is_empty())
+        .map(|key| key.parse().map_err(|_| CliError::Usage(format!("{}: `{}` is not an integer key", origin, key))))
+        .collect()
+}
+
+fn read_keys(sources: &[KeySource]) -> Result<Vec<i64>, CliError> {
+    let mut keys = Vec::new();
+    for source in sources {
+        match source {
+            KeySource::List(list) => keys.extend(parse_keys(list, "keys")?),
+            KeySource::File(path) => {
+                let text = fs::read_to_string(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+                keys.extend(parse_keys(&text, path)?);
+            }
+            KeySource::Stdin => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                keys.extend(parse_keys(&text, "standard input")?);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+fn run_draw(options: DrawOptions) -> Result<(), CliError> {
+    let (keys, deletes) = if options.keys.is_empty() {
+        (DEMO_KEYS.to_vec(), options.deletes.unwrap_or_else(|| DEMO_DELETES.to_vec()))
+    } else {
+        (read_keys(&options.keys)?, options.deletes.unwrap_or_default())
+    };
+    fs::create_dir_all(&options.out_dir)?;
+    let out_file = |name: &str| Dotfile::new(&Path::new(&options.out_dir).join(format!("{}.{}", name, options.format.extension())).to_string_lossy());
+    let check = |tree: &RedBlackTreeStructure<i64>| -> Result<(), CliError> {
+        if options.validate {
+            tree.validate()?;
+            tree.audit()?;
+            println!("Tree is valid");
+        }
+        Ok(())
+    };
+
+    let mut rb_tree = RedBlackTreeStructure::new();
+    for key in keys {
+        rb_tree.insert(key);
+    }
+    let count = RedBlackTreeStructure::<i64>::number_of_leaves(&rb_tree.root);
+    let height = RedBlackTreeStructure::height_of_tree(&rb_tree.root);
+    if options.dump {
+        println!("{:#?}", rb_tree.root);
+    }
+    println!("Leaf Count:{}", count);
+    println!("Height:{}", height);
+    println!("Tree traversal");
+    RedBlackTreeStructure::in_order_traversal(&rb_tree.root);
+    println!("Is tree Empty:{}", rb_tree.tree_is_empty());
+    check(&rb_tree)?;
+    let mut dot_file = out_file("rbt");
+    rb_tree.draw_tree(&mut dot_file);
+    options.format.write(&dot_file)?;
+    if deletes.is_empty() {
+        return Ok(());
+    }
+
+    let before_delete = rb_tree.clone();
+    for key in deletes {
+        if !rb_tree.delete(key) {
+            eprintln!("warning: {} is not in the tree", key);
+        }
+    }
+    check(&rb_tree)?;
+    let mut dot_file = out_file("rbt_after_delete");
+    rb_tree.draw_tree(&mut dot_file);
+    options.format.write(&dot_file)?;
+    let mut dot_file = out_file("rbt_diff");
+    print!("{}", diff::draw_diff(&before_delete, &rb_tree, &mut dot_file));
+    options.format.write(&dot_file)?;
+    if options.dump {
+        println!("{:#?}", rb_tree.root);
+    }
+    RedBlackTreeStructure::in_order_traversal(&rb_tree.root);
+    Ok(())
+}
+
+fn run_metrics(size: usize, seed: u64, every: usize, out_dir: &str) -> Result<(), CliError> {
+    fs::create_dir_all(out_dir)?;
+    let path = Path::new(out_dir).join("metrics.csv");
+    let mut tree = RedBlackTreeStructure::new();
+    let series = tree.run_workload(metrics::insert_then_remove(size, seed), every);
+    series.write_csv(&path.to_string_lossy())?;
+    println!("Wrote {} samples to {}", series.samples.len(), path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitCode;
+
+    use super::{parse, parse_keys, read_keys, run, CliError, Command, Format, KeySource};
+    use crate::error::TreeError;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn usage_error(line: &str) -> String {
+        match parse(args(line)) {
+            Err(CliError::Usage(message)) => message,
+            Err(CliError::Tree(err)) => panic!("{:?} failed with {}", line, err),
+            Ok(_) => panic!("{:?} was accepted", line),
+        }
+    }
+
+    #[test]
+    fn subcommands_are_dispatched() {
+        assert!(matches!(parse(args("help")), Ok(Command::Help)));
+        assert!(matches!(parse(args("bench --help")), Ok(Command::Help)));
+        assert!(matches!(parse(args("bench --size 10 --seed 7")), Ok(Command::Bench { size: 10, seed: 7 })));
+        match parse(args("metrics --every=5 --out-dir out")) {
+            Ok(Command::Metrics { every, out_dir, .. }) => assert_eq!((every, out_dir.as_str()), (5, "out")),
+            _ => panic!("metrics was not parsed"),
+        }
+        // Without a subcommand the arguments belong to draw.
+        match parse(args("3 1 --keys=4,5 --stdin --delete 1 --delete=3 --format mermaid --no-dump --validate")) {
+            Ok(Command::Draw(options)) => {
+                assert!(matches!(options.keys.as_slice(), [KeySource::List(first), KeySource::List(second), KeySource::Stdin] if first == "3 1" && second == "4,5"));
+                assert_eq!(options.deletes, Some(vec![1, 3]));
+                assert_eq!(options.format, Format::Mermaid);
+                assert!(!options.dump && options.validate);
+            }
+            _ => panic!("draw was not parsed"),
+        }
+        match parse(Vec::new()) {
+            Ok(Command::Draw(options)) => assert!(options.keys.is_empty() && options.deletes.is_none() && options.dump),
+            _ => panic!("an empty command line is the demo"),
+        }
+    }
+
+    #[test]
+    fn malformed_command_lines_are_usage_errors() {
+        assert_eq!(usage_error("--validate=yes"), "--validate does not take a value");
+        assert_eq!(usage_error("draw --keys"), "--keys needs a value");
+        assert_eq!(usage_error("--verbose"), "unknown option --verbose");
+        assert_eq!(usage_error("bench --every 3"), "unknown option --every for bench");
+        assert_eq!(usage_error("metrics 12"), "unexpected argument `12`");
+        assert_eq!(usage_error("bench --size=-1"), "--size expects a non-negative integer, got `-1`");
+        assert_eq!(usage_error("--format svg"), "unknown format `svg`, expected dot, mermaid or graphml");
+        assert_eq!(usage_error("--delete 1,x"), "--delete: `x` is not an integer key");
+    }
+
+    #[test]
+    fn keys_are_split_on_commas_and_whitespace() {
+        assert_eq!(parse_keys(" 1,2\n-3 ,, 4\t", "test").ok(), Some(vec![1, 2, -3, 4]));
+        assert_eq!(parse_keys("", "test").ok(), Some(Vec::new()));
+        assert!(matches!(parse_keys("1 2.5", "test"), Err(CliError::Usage(message)) if message == "test: `2.5` is not an integer key"));
+    }
+
+    #[test]
+    fn exit_codes_tell_usage_errors_from_failures() {
+        assert_eq!(run(args("--no-such-flag")), ExitCode::from(2));
+        assert_eq!(run(args("draw 1 x")), ExitCode::from(2));
+        assert_eq!(run(args("--keys-file no/such/file --no-dump")), ExitCode::FAILURE);
+        assert_eq!(run(args("help")), ExitCode::SUCCESS);
+        assert!(matches!(
+            read_keys(&[KeySource::File("no/such/file".to_string())]),
+            Err(CliError::Tree(TreeError::Io(_)))
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use crate::persistent::PersistentRedBlackTree;
+
+/// A red-black tree that can be shared between threads: many readers alongside one writer.
+///
+/// The current version is a `PersistentRedBlackTree` behind a lock that is only held long enough
+/// to clone or swap it. Readers take a `snapshot` and query it without blocking anybody, and
+/// always see one consistent version. Writers are serialised by a separate mutex, build the next
+/// version from the current one, and publish it with a single swap.
+#[derive(Debug)]
+pub struct ConcurrentRedBlackTree<T> {
+    current: RwLock<PersistentRedBlackTree<T>>,
+    writer: Mutex<()>,
+}
+
+impl<T> Default for ConcurrentRedBlackTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ConcurrentRedBlackTree<T> {
+    pub fn new() -> Self {
+        ConcurrentRedBlackTree { current: RwLock::new(PersistentRedBlackTree::new()), writer: Mutex::new(()) }
+    }
+
+    /// The latest published version. Later writes do not affect the returned tree.
+    pub fn snapshot(&self) -> PersistentRedBlackTree<T> {
+        // The guarded value is only ever replaced whole, so a poisoned lock still holds a valid tree.
+        self.current.read().unwrap_or_else(PoisonError::into_inner).snapshot()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    /// Runs `update` on the latest version and publishes its result, holding the writer lock so
+    /// concurrent updates are applied one after another rather than lost.
+    pub fn update<F: FnOnce(&PersistentRedBlackTree<T>) -> PersistentRedBlackTree<T>>(&self, update: F) {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let next = update(&self.snapshot());
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = next;
+    }
+}
+
+impl<T: Ord> ConcurrentRedBlackTree<T> {
+    pub fn contains(&self, key: &T) -> bool {
+        self.snapshot().contains(key)
+    }
+}
+
+impl<T: Ord + Clone> ConcurrentRedBlackTree<T> {
+    /// Returns `true` if the key was not present before.
+    pub fn insert(&self, key: T) -> bool {
+        let mut inserted = false;
+        self.update(|tree| {
+            let next = tree.insert(key);
+            inserted = next.len() > tree.len();
+            next
+        });
+        inserted
+    }
+
+    /// Returns `true` if the key was present.
+    pub fn remove(&self, key: &T) -> bool {
+        let mut removed = false;
+        self.update(|tree| {
+            let next = tree.remove(key);
+            removed = next.len() < tree.len();
+            next
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    use super::ConcurrentRedBlackTree;
+    use crate::bench::workload;
+
+    const WRITERS: u64 = 4;
+    const STEPS: usize = 1000;
+
+    #[test]
+    fn readers_only_ever_see_consistent_versions() {
+        let tree = ConcurrentRedBlackTree::new();
+        let done = AtomicBool::new(false);
+        let model = thread::scope(|scope| {
+            let readers: Vec<_> = (0..3)
+                .map(|_| {
+                    scope.spawn(|| {
+                        loop {
+                            let finished = done.load(Ordering::Acquire);
+                            let snapshot = tree.snapshot();
+                            assert_eq!(snapshot.len(), snapshot.range(..).len());
+                            assert!(snapshot.validate().is_ok());
+                            if finished {
+                                break;
+                            }
+                        }
+                    })
+                })
+                .collect();
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|writer| {
+                    let tree = &tree;
+                    scope.spawn(move || {
+                        // Each writer owns the keys congruent to it, so its own model predicts
+                        // what every insert and remove returns.
+                        let mut model = BTreeSet::new();
+                        for step in workload(STEPS, 32 + writer) {
+                            let key = step % 200 * WRITERS + writer;
+                            if (step >> 20) % 3 == 0 {
+                                assert_eq!(tree.remove(&key), model.remove(&key));
+                            } else {
+                                assert_eq!(tree.insert(key), model.insert(key));
+                            }
+                        }
+                        model
+                    })
+                })
+                .collect();
+            let model: BTreeSet<u64> = writers.into_iter().flat_map(|writer| writer.join().expect("writer panicked")).collect();
+            done.store(true, Ordering::Release);
+            for reader in readers {
+                reader.join().expect("reader panicked");
+            }
+            model
+        });
+
+        let last = tree.snapshot();
+        assert!(last.validate().is_ok());
+        assert!(last.range(..).into_iter().eq(model.iter()));
+        assert_eq!(tree.len(), model.len());
+        for key in 0..200 * WRITERS {
+            assert_eq!(tree.contains(&key), model.contains(&key));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::cell::Ref;
+
+use crate::error::intact;
+use crate::{Aggregate, Compare, RedBlackTree, RedBlackTreeStructure, TreeNode};
+
+/// A cursor's place in key order together with its two neighbours.
+///
+/// `current == None` is the "ghost" position between the last and the first key, so moving past
+/// either end and then back again returns to where the walk left off. The neighbours are cached so
+/// `peek_next`/`peek_prev` can hand out borrows; they are found through the nodes' parent links.
+struct Position<T, S> {
+    current: RedBlackTree<T, S>,
+    prev: RedBlackTree<T, S>,
+    next: RedBlackTree<T, S>,
+}
+
+impl<T, S> Position<T, S> {
+    fn new<C, A>(tree: &RedBlackTreeStructure<T, C, A>, current: RedBlackTree<T, S>) -> Self
+    where
+        C: Compare<T>,
+        A: Aggregate<T, Value = S>,
+    {
+        let (prev, next) = match &current {
+            Some(node) => (TreeNode::predecessor(node), TreeNode::successor(node)),
+            None => (tree.last_node(), tree.first_node()),
+        };
+        Position { current, prev, next }
+    }
+
+    fn key(&self) -> Option<Ref<'_, T>> {
+        Self::borrow_key(&self.current)
+    }
+
+    fn borrow_key(node: &RedBlackTree<T, S>) -> Option<Ref<'_, T>> {
+        node.as_ref().map(|node| Ref::map(node.borrow(), |node| node.key()))
+    }
+}
+
+/// A read-only cursor over a `RedBlackTreeStructure`, created by `cursor_front`, `cursor_back`
+/// or `cursor_at`.
+pub struct Cursor<'a, T, C, A: Aggregate<T> = ()> {
+    tree: &'a RedBlackTreeStructure<T, C, A>,
+    position: Position<T, A::Value>,
+}
+
+/// A cursor that can also remove the key it points at, rebalancing the tree as it goes.
+pub struct CursorMut<'a, T, C, A: Aggregate<T> = ()> {
+    tree: &'a mut RedBlackTreeStructure<T, C, A>,
+    position: Position<T, A::Value>,
+}
+
+#[allow(dead_code, reason = "the demo walks the tree recursively")]
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// A cursor at the smallest key, or at the ghost position if the tree is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, T, C, A> {
+        Cursor { position: Position::new(self, self.first_node()), tree: self }
+    }
+
+    pub fn cursor_back(&self) -> Cursor<'_, T, C, A> {
+        Cursor { position: Position::new(self, self.last_node()), tree: self }
+    }
+
+    /// A cursor at `key`, or at the ghost position if the key is not in the tree.
+    pub fn cursor_at(&self, key: &T) -> Cursor<'_, T, C, A> {
+        Cursor { position: Position::new(self, self.find_node(key)), tree: self }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T, C, A> {
+        let position = Position::new(self, self.first_node());
+        CursorMut { tree: self, position }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T, C, A> {
+        let position = Position::new(self, self.last_node());
+        CursorMut { tree: self, position }
+    }
+
+    pub fn cursor_at_mut(&mut self, key: &T) -> CursorMut<'_, T, C, A> {
+        let position = Position::new(self, self.find_node(key));
+        CursorMut { tree: self, position }
+    }
+
+    fn first_node(&self) -> RedBlackTree<T, A::Value> {
+        self.root.clone().map(|root| self.find_mininmum_node(root))
+    }
+
+    fn last_node(&self) -> RedBlackTree<T, A::Value> {
+        self.root.clone().map(|root| self.find_maximum_node(root))
+    }
+}
+
+#[allow(dead_code, reason = "cursor API")]
+impl<T, C: Compare<T>, A: Aggregate<T>> Cursor<'_, T, C, A> {
+    /// The key under the cursor, `None` at the ghost position.
+    pub fn key(&self) -> Option<Ref<'_, T>> {
+        self.position.key()
+    }
+
+    pub fn peek_next(&self) -> Option<Ref<'_, T>> {
+        Position::borrow_key(&self.position.next)
+    }
+
+    pub fn peek_prev(&self) -> Option<Ref<'_, T>> {
+        Position::borrow_key(&self.position.prev)
+    }
+
+    pub fn move_next(&mut self) {
+        self.position = Position::new(self.tree, self.position.next.take());
+    }
+
+    pub fn move_prev(&mut self) {
+        self.position = Position::new(self.tree, self.position.prev.take());
+    }
+}
+
+#[allow(dead_code, reason = "cursor API")]
+impl<T, C: Compare<T>, A: Aggregate<T>> CursorMut<'_, T, C, A> {
+    pub fn key(&self) -> Option<Ref<'_, T>> {
+        self.position.key()
+    }
+
+    pub fn peek_next(&self) -> Option<Ref<'_, T>> {
+        Position::borrow_key(&self.position.next)
+    }
+
+    pub fn peek_prev(&self) -> Option<Ref<'_, T>> {
+        Position::borrow_key(&self.position.prev)
+    }
+
+    pub fn move_next(&mut self) {
+        self.position = Position::new(self.tree, self.position.next.take());
+    }
+
+    pub fn move_prev(&mut self) {
+        self.position = Position::new(self.tree, self.position.prev.take());
+    }
+
+    /// Removes the key under the cursor and moves on to the next one. Every key keeps its node
+    /// across the delete, so the cached neighbours stay valid. Does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.position.current.take()?;
+        let next = self.position.next.take();
+        self.tree.record();
+        let key = intact(self.tree.take_node(node));
+        self.position = Position::new(self.tree, next);
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RedBlackTreeStructure;
+
+    #[test]
+    fn remove_current_walks_on_past_removed_nodes() {
+        let mut tree: RedBlackTreeStructure<i32> = (0..20).collect();
+        std::mem::forget(tree.cursor_at(&4));
+        let mut cursor = tree.cursor_front_mut();
+        let mut removed = Vec::new();
+        while let Some(key) = cursor.key().map(|key| *key) {
+            if key % 2 == 0 {
+                removed.push(cursor.remove_current().expect("cursor is on a key"));
+            } else {
+                cursor.move_next();
+            }
+        }
+        assert!(cursor.key().is_none());
+        cursor.move_prev();
+        assert_eq!(cursor.key().map(|key| *key), Some(19));
+        assert_eq!(removed, (0..20).step_by(2).collect::<Vec<_>>());
+        assert_eq!(tree.inorder().collect::<Vec<_>>(), (1..20).step_by(2).collect::<Vec<_>>());
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn read_only_cursor_wraps_through_the_ghost_position() {
+        let tree: RedBlackTreeStructure<i32> = [3, 1, 2].into_iter().collect();
+        let mut cursor = tree.cursor_back();
+        assert_eq!(cursor.peek_prev().map(|key| *key), Some(2));
+        cursor.move_next();
+        assert!(cursor.key().is_none());
+        assert_eq!(cursor.peek_next().map(|key| *key), Some(1));
+        cursor.move_next();
+        assert_eq!(cursor.key().map(|key| *key), Some(1));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.key().map(|key| *key), Some(3));
+        assert_eq!(tree.cursor_front().key().map(|key| *key), Some(1));
+    }
+
+    #[test]
+    fn mutable_cursors_start_where_asked() {
+        let mut tree: RedBlackTreeStructure<i32> = (1..=5).collect();
+        let mut cursor = tree.cursor_at_mut(&3);
+        assert_eq!((cursor.peek_prev().map(|key| *key), cursor.peek_next().map(|key| *key)), (Some(2), Some(4)));
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.key().map(|key| *key), Some(4));
+        assert!(tree.cursor_at_mut(&3).key().is_none());
+        let mut cursor = tree.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(5));
+        assert!(cursor.key().is_none());
+        assert_eq!(cursor.peek_next().map(|key| *key), Some(1));
+        assert_eq!(tree.inorder().collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert!(tree.validate().is_ok());
+    }
+}
@@ -0,0 +1,289 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::{draw_node_with, Aggregate, Compare, Dotfile, NodeColor, RedBlackTreeStructure, Tree, TreeNode};
+
+/// Where a node hangs in its tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Placement<T> {
+    Root,
+    /// Left child of the node holding this key.
+    LeftOf(T),
+    RightOf(T),
+}
+
+/// A key whose node is red in one tree and black in the other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recolor<T> {
+    pub key: T,
+    pub before: NodeColor,
+    pub after: NodeColor,
+}
+
+/// A key whose node has a different parent, or is on the other side of it, in the second tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move<T> {
+    pub key: T,
+    pub before: Placement<T>,
+    pub after: Placement<T>,
+}
+
+/// What changed between two trees, each list in ascending key order. See `diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    pub recolored: Vec<Recolor<T>>,
+    pub moved: Vec<Move<T>>,
+}
+
+impl<T> TreeDiff<T> {
+    /// `true` if both trees hold the same keys in the same shape and colours.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.recolored.is_empty() && self.moved.is_empty()
+    }
+}
+
+/// One node of a tree being compared, with what `diff` looks at.
+struct Snapshot<T, S> {
+    node: Tree<T, S>,
+    color: NodeColor,
+    parent: Option<(Tree<T, S>, bool)>,
+}
+
+fn snapshots<T, C: Compare<T>, A: Aggregate<T>>(tree: &RedBlackTreeStructure<T, C, A>) -> Vec<Snapshot<T, A::Value>> {
+    let mut snapshots = Vec::with_capacity(tree.len);
+    let mut nodes = tree.inorder();
+    while let Some(node) = nodes.next_node() {
+        let parent = TreeNode::parent_of(&node).map(|parent| {
+            let left = parent.borrow().left.as_ref().is_some_and(|left| Rc::ptr_eq(left, &node));
+            (parent, left)
+        });
+        let color = node.borrow().color.clone();
+        snapshots.push(Snapshot { node, color, parent });
+    }
+    snapshots
+}
+
+fn placement<T: Clone, S>(parent: &Option<(Tree<T, S>, bool)>) -> Placement<T> {
+    match parent {
+        None => Placement::Root,
+        Some((parent, true)) => Placement::LeftOf(parent.borrow().key().clone()),
+        Some((parent, false)) => Placement::RightOf(parent.borrow().key().clone()),
+    }
+}
+
+/// Compares `before` with `after` key by key: which keys only one of them holds, and which of the
+/// keys they share changed colour or position. Keys are matched with `before`'s comparator, in a
+/// single O(n) merge of the two in-order sequences.
+pub fn diff<T, C, A>(before: &RedBlackTreeStructure<T, C, A>, after: &RedBlackTreeStructure<T, C, A>) -> TreeDiff<T>
+where
+    T: Clone,
+    C: Compare<T>,
+    A: Aggregate<T>,
+{
+    let mut diff = TreeDiff { added: Vec::new(), removed: Vec::new(), recolored: Vec::new(), moved: Vec::new() };
+    let (old, new) = (snapshots(before), snapshots(after));
+    let (mut old, mut new) = (old.iter().peekable(), new.iter().peekable());
+    loop {
+        let order = match (old.peek(), new.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => before.compare.compare(a.node.borrow().key(), b.node.borrow().key()),
+        };
+        match order {
+            Ordering::Less => {
+                let gone = old.next().expect("peeked above");
+                diff.removed.push(gone.node.borrow().key().clone());
+            }
+            Ordering::Greater => {
+                let new_node = new.next().expect("peeked above");
+                diff.added.push(new_node.node.borrow().key().clone());
+            }
+            Ordering::Equal => {
+                let (a, b) = (old.next().expect("peeked above"), new.next().expect("peeked above"));
+                let key = b.node.borrow().key().clone();
+                if a.color != b.color {
+                    diff.recolored.push(Recolor { key: key.clone(), before: a.color.clone(), after: b.color.clone() });
+                }
+                let same_place = match (&a.parent, &b.parent) {
+                    (None, None) => true,
+                    (Some((p, p_left)), Some((q, q_left))) => {
+                        p_left == q_left && before.compare.compare(p.borrow().key(), q.borrow().key()) == Ordering::Equal
+                    }
+                    _ => false,
+                };
+                if !same_place {
+                    diff.moved.push(Move { key, before: placement(&a.parent), after: placement(&b.parent) });
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Draws `after` with the changes since `before` marked: added nodes outlined green, recoloured
+/// ones orange and moved ones blue, and removed keys as dashed nodes hanging off their former
+/// parent, if it is still in the tree or was removed as well. Returns the diff it drew.
+pub fn draw_diff<T, C, A>(before: &RedBlackTreeStructure<T, C, A>, after: &RedBlackTreeStructure<T, C, A>, file: &mut Dotfile) -> TreeDiff<T>
+where
+    T: Clone + fmt::Debug,
+    C: Compare<T>,
+    A: Aggregate<T>,
+{
+    let diff = diff(before, after);
+    let mut drawn = HashMap::new();
+    draw_node_with(after.root.clone(), file, None, &mut |node: &Tree<T, A::Value>, idx| {
+        drawn.insert(Rc::as_ptr(node), idx);
+    });
+    let idx_of = |key: &T| after.find_node(key).map(|node| drawn[&Rc::as_ptr(&node)]);
+
+    // A node both moved and recoloured shows as recoloured.
+    let marked = diff.moved.iter().map(|moved| (&moved.key, "blue"))
+        .chain(diff.recolored.iter().map(|recolor| (&recolor.key, "orange")))
+        .chain(diff.added.iter().map(|key| (key, "green")));
+    for (key, color) in marked {
+        if let Some(idx) = idx_of(key) {
+            file.highlight_node(idx, color);
+        }
+    }
+    let mut ghosts = Vec::with_capacity(diff.removed.len());
+    let mut ghost_idx = HashMap::new();
+    for key in &diff.removed {
+        let old = before.find_node(key).expect("removed keys come from `before`");
+        let idx = file.add_node(&format!("{:?}", key), old.borrow().color.clone());
+        file.dash_node(idx);
+        ghost_idx.insert(Rc::as_ptr(&old), idx);
+        ghosts.push((old, idx));
+    }
+    // A removed node's parent may have been removed too; then it hangs off that one's ghost.
+    for (old, idx) in &ghosts {
+        let former_parent = TreeNode::parent_of(old).and_then(|parent| {
+            ghost_idx.get(&Rc::as_ptr(&parent)).copied().or_else(|| idx_of(parent.borrow().key()))
+        });
+        if let Some(parent_idx) = former_parent {
+            file.add_dashed_edge(parent_idx, *idx);
+        }
+    }
+    diff
+}
+
+impl<T: fmt::Debug> fmt::Display for Placement<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::Root => write!(f, "root"),
+            Placement::LeftOf(parent) => write!(f, "left of {:?}", parent),
+            Placement::RightOf(parent) => write!(f, "right of {:?}", parent),
+        }
+    }
+}
+
+/// One line per kind of change, e.g. `moved: 15 (left of 20 -> root)`.
+impl<T: fmt::Debug> fmt::Display for TreeDiff<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        fn line<I: IntoIterator<Item = String>>(f: &mut fmt::Formatter<'_>, what: &str, items: I) -> fmt::Result {
+            let items: Vec<String> = items.into_iter().collect();
+            if items.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{}: {}", what, items.join(", "))
+        }
+        let color = |color: &NodeColor| match color {
+            NodeColor::Red => "red",
+            NodeColor::Black => "black",
+        };
+        line(f, "added", self.added.iter().map(|key| format!("{:?}", key)))?;
+        line(f, "removed", self.removed.iter().map(|key| format!("{:?}", key)))?;
+        line(f, "recolored", self.recolored.iter().map(|r| format!("{:?} ({} -> {})", r.key, color(&r.before), color(&r.after))))?;
+        line(f, "moved", self.moved.iter().map(|m| format!("{:?} ({} -> {})", m.key, m.before, m.after)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, draw_diff, Move, Placement, Recolor};
+    use crate::{Dotfile, NodeColor, RedBlackTreeStructure};
+
+    /// The demo tree of `main` before and after its deletes.
+    fn demo() -> (RedBlackTreeStructure<i32>, RedBlackTreeStructure<i32>) {
+        let mut before = RedBlackTreeStructure::new();
+        for key in [10, 20, 30, 15, 25, 5, 6, 1, 43] {
+            before.insert(key);
+        }
+        let mut after = before.clone();
+        for key in [5, 30, 10, 43] {
+            after.delete(key);
+        }
+        (before, after)
+    }
+
+    fn idx_of(file: &Dotfile, label: &str) -> usize {
+        file.nodes.iter().position(|node| node.fields == [label]).expect("node is drawn")
+    }
+
+    #[test]
+    fn deletes_show_up_as_removed_recolored_and_moved_keys() {
+        let (before, after) = demo();
+        let changes = diff(&before, &after);
+        assert_eq!(changes.added, Vec::<i32>::new());
+        assert_eq!(changes.removed, vec![5, 10, 30, 43]);
+        assert_eq!(
+            changes.recolored,
+            vec![
+                Recolor { key: 1, before: NodeColor::Red, after: NodeColor::Black },
+                Recolor { key: 25, before: NodeColor::Red, after: NodeColor::Black },
+            ]
+        );
+        assert_eq!(
+            changes.moved,
+            vec![
+                Move { key: 1, before: Placement::LeftOf(5), after: Placement::LeftOf(6) },
+                Move { key: 6, before: Placement::RightOf(5), after: Placement::LeftOf(20) },
+                Move { key: 15, before: Placement::RightOf(10), after: Placement::RightOf(6) },
+                Move { key: 25, before: Placement::LeftOf(30), after: Placement::RightOf(20) },
+            ]
+        );
+        let back = diff(&after, &before);
+        assert_eq!(back.added, changes.removed);
+        assert!(diff(&after, &after.clone()).is_empty());
+    }
+
+    #[test]
+    fn removed_nodes_hang_dashed_off_their_former_parent_or_its_ghost() {
+        let (before, after) = demo();
+        let mut file = Dotfile::new("unused");
+        draw_diff(&before, &after, &mut file);
+        for removed in ["5", "10", "30", "43"] {
+            assert!(file.nodes[idx_of(&file, removed)].dashed, "{} is not dashed", removed);
+        }
+        let dashed_edges: Vec<(usize, usize)> = file.edges.iter().filter(|edge| edge.dashed).map(|edge| (edge.src_id, edge.dest_id)).collect();
+        let edge = |parent: &str, child: &str| (idx_of(&file, parent), idx_of(&file, child));
+        // 10 and 30 were children of 20, which is still there; 5 and 43 hang off the ghosts of 10 and 30.
+        assert_eq!(dashed_edges.len(), 4);
+        for (parent, child) in [("20", "10"), ("20", "30"), ("10", "5"), ("30", "43")] {
+            assert!(dashed_edges.contains(&edge(parent, child)), "{} -- {}", parent, child);
+        }
+        let outline = |label: &str| file.nodes[idx_of(&file, label)].outline.clone();
+        assert_eq!(outline("1").as_deref(), Some("orange"));
+        assert_eq!(outline("6").as_deref(), Some("blue"));
+        assert_eq!(outline("20"), None);
+    }
+
+    #[test]
+    fn inserted_nodes_are_outlined_green() {
+        let (_, before) = demo();
+        let mut after = before.clone();
+        after.insert(26);
+        let mut file = Dotfile::new("unused");
+        let changes = draw_diff(&before, &after, &mut file);
+        assert_eq!(changes.added, vec![26]);
+        assert_eq!(file.nodes[idx_of(&file, "26")].outline.as_deref(), Some("green"));
+        assert!(file.nodes.iter().all(|node| !node.dashed));
+    }
+}
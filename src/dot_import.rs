@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::error::TreeError;
+use crate::{Aggregate, Compare, NodeColor, RedBlackTreeStructure, Tree, TreeNode};
+
+/// One `idx [label=..., color=...]` line of a dot file.
+struct ParsedNode {
+    /// The first line of the label; any further lines are a `NodeAnnotation`.
+    label: String,
+    color: NodeColor,
+}
+
+/// One `src -- dest` or `src:port -> dest` line.
+struct ParsedEdge {
+    dest: usize,
+    /// `Some(true)` for an edge leaving from the "sw" port, i.e. to a left child.
+    left: Option<bool>,
+}
+
+/// The nodes and edges of a dot file in the format `Dotfile::write_file` produces for
+/// `draw_tree`, in file order.
+struct ParsedGraph {
+    nodes: HashMap<usize, ParsedNode>,
+    node_order: Vec<usize>,
+    /// Outgoing edges per node, in file order: `draw_node` writes the left edge first.
+    children: HashMap<usize, Vec<ParsedEdge>>,
+}
+
+fn corrupt(line_no: usize, what: &str) -> TreeError {
+    TreeError::Corrupt(format!("line {}: {}", line_no, what))
+}
+
+impl ParsedGraph {
+    fn parse(text: &str) -> Result<Self, TreeError> {
+        let mut graph = ParsedGraph { nodes: HashMap::new(), node_order: Vec::new(), children: HashMap::new() };
+        let mut edge_op = None;
+        for (line_idx, line) in text.lines().enumerate() {
+            let line_no = line_idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line == "}" || line.starts_with("subgraph ") || line.starts_with("label=") {
+                continue;
+            }
+            if line == "graph {" || line == "digraph {" {
+                if edge_op.is_some() {
+                    return Err(corrupt(line_no, "second graph"));
+                }
+                edge_op = Some(if line == "graph {" { " -- " } else { " -> " });
+                continue;
+            }
+            let Some(edge_op) = edge_op else {
+                return Err(corrupt(line_no, "expected `graph {` or `digraph {`"));
+            };
+            let statement = line.strip_suffix(';').ok_or_else(|| corrupt(line_no, "missing `;`"))?;
+            if let Some((src, dest)) = statement.split_once(edge_op) {
+                let (src, left) = match src.split_once(':') {
+                    Some((src, "sw")) => (src, Some(true)),
+                    Some((src, "se")) => (src, Some(false)),
+                    Some((_, port)) => return Err(corrupt(line_no, &format!("unknown port `{}`", port))),
+                    None => (src, None),
+                };
+                let src = Self::parse_idx(src, line_no)?;
+                // Drop the attributes of an emphasised edge.
+                let dest = dest.split_once(" [").map_or(dest, |(dest, _)| dest);
+                let dest = Self::parse_idx(dest, line_no)?;
+                graph.children.entry(src).or_default().push(ParsedEdge { dest, left });
+            } else if let Some((idx, attrs)) = statement.split_once(" [") {
+                let idx = Self::parse_idx(idx, line_no)?;
+                let attrs = attrs.strip_suffix(']').ok_or_else(|| corrupt(line_no, "missing `]`"))?;
+                let node = Self::parse_node(attrs, line_no)?;
+                if graph.nodes.insert(idx, node).is_some() {
+                    return Err(corrupt(line_no, "node declared twice"));
+                }
+                graph.node_order.push(idx);
+            } else {
+                return Err(corrupt(line_no, "neither a node nor an edge"));
+            }
+        }
+        if edge_op.is_none() {
+            return Err(TreeError::Corrupt("no `graph {` or `digraph {` found".to_string()));
+        }
+        Ok(graph)
+    }
+
+    fn parse_idx(text: &str, line_no: usize) -> Result<usize, TreeError> {
+        text.trim().parse().map_err(|_| corrupt(line_no, "node id is not a number"))
+    }
+
+    /// Reads `label="..."` and the colour from `class`, `fillcolor` or `color`; the other
+    /// attributes only affect rendering.
+    fn parse_node(attrs: &str, line_no: usize) -> Result<ParsedNode, TreeError> {
+        let label_start = attrs.strip_prefix("label=\"").ok_or_else(|| corrupt(line_no, "node does not start with a label"))?;
+        let (label, rest) = label_start.split_once('"').ok_or_else(|| corrupt(line_no, "unterminated label"))?;
+        let (mut color, mut fill, mut class) = (None, None, None);
+        for attr in rest.split(',').map(str::trim).filter(|attr| !attr.is_empty()) {
+            match attr.split_once('=') {
+                Some(("color", value)) => color = Some(value),
+                Some(("fillcolor", value)) => fill = Some(value),
+                Some(("class", value)) => class = Some(value),
+                Some(("shape", "record")) => return Err(corrupt(line_no, "record-shaped nodes are not part of a red-black tree")),
+                _ => {}
+            }
+        }
+        // A custom `DotStyle` fill comes with a class naming the colour it stands for, and a
+        // highlighted node keeps its fill in `fillcolor`.
+        let color = match class.or(fill).or(color) {
+            Some("red") => NodeColor::Red,
+            Some("black") => NodeColor::Black,
+            Some(other) => return Err(corrupt(line_no, &format!("unknown color `{}`", other))),
+            None => return Err(corrupt(line_no, "node has no color")),
+        };
+        let label = label.split("\\n").next().unwrap_or_default();
+        Ok(ParsedNode { label: label.to_string(), color })
+    }
+}
+
+#[allow(dead_code, reason = "the demo only writes drawings")]
+impl<T, C, A> RedBlackTreeStructure<T, C, A>
+where
+    T: FromStr,
+    C: Compare<T> + Default,
+    A: Aggregate<T> + Default,
+{
+    /// Loads a tree from a file written by `draw_tree` and `Dotfile::write_file`.
+    pub fn from_dot_file(path: &str) -> Result<Self, TreeError> {
+        Self::from_dot_str(&fs::read_to_string(path)?)
+    }
+
+    /// Rebuilds the tree drawn in `text` node for node, colours included, then validates it.
+    ///
+    /// Reads every `DotStyle`: a "None" node without edges is a NIL placeholder, whether each
+    /// empty slot has its own or they share one. Without ports a key node needs both child edges,
+    /// left first, or none; with the "sw"/"se" ports of a directed drawing either may be missing,
+    /// as under `NilLeaves::Hidden`. Labels are parsed back into keys with `FromStr`, ignoring any
+    /// annotation line. A malformed file is reported as `TreeError::Corrupt`, and a well-formed
+    /// drawing of something that is not a red-black tree as `TreeError::InvariantViolated`.
+    pub fn from_dot_str(text: &str) -> Result<Self, TreeError> {
+        let graph = ParsedGraph::parse(text)?;
+        let mut tree = Self::default();
+        let is_nil = |idx: &usize| graph.nodes.get(idx).is_some_and(|node| node.label == "None") && !graph.children.contains_key(idx);
+
+        let mut handles: HashMap<usize, Tree<T, A::Value>> = HashMap::new();
+        for &idx in graph.node_order.iter().filter(|idx| !is_nil(idx)) {
+            let node = &graph.nodes[&idx];
+            let key = node.label.parse().map_err(|_| TreeError::Corrupt(format!("label `{}` of node {} is not a valid key", node.label, idx)))?;
+            let handle = TreeNode::new(key, tree.aggregate.identity());
+            handle.borrow_mut().color = node.color.clone();
+            handles.insert(idx, handle);
+        }
+
+        // Check the shape on the ids before linking anything, so a malformed file (a cycle, say)
+        // never turns into a tree of `Rc`s.
+        let mut slots: HashMap<usize, [Option<usize>; 2]> = HashMap::new();
+        let mut has_parent = HashMap::new();
+        for (&src, edges) in &graph.children {
+            if !handles.contains_key(&src) {
+                return Err(TreeError::Corrupt(format!("edge from undeclared or NIL node {}", src)));
+            }
+            let sides = Self::child_sides(src, edges)?;
+            let mut children = [None, None];
+            for (edge, left) in edges.iter().zip(sides) {
+                if !graph.nodes.contains_key(&edge.dest) {
+                    return Err(TreeError::Corrupt(format!("edge to undeclared node {}", edge.dest)));
+                }
+                if is_nil(&edge.dest) {
+                    continue;
+                }
+                if has_parent.insert(edge.dest, src).is_some() {
+                    return Err(TreeError::Corrupt(format!("node {} has more than one parent", edge.dest)));
+                }
+                children[usize::from(!left)] = Some(edge.dest);
+            }
+            slots.insert(src, children);
+        }
+        let mut roots = graph.node_order.iter().copied().filter(|idx| handles.contains_key(idx) && !has_parent.contains_key(idx));
+        let root = match (roots.next(), roots.next()) {
+            (None, _) => None,
+            (Some(root), None) => Some(root),
+            (Some(_), Some(_)) => return Err(TreeError::Corrupt("the graph has more than one root".to_string())),
+        };
+        // Parents come before their children in this walk, so refreshing the summaries in
+        // reverse order below sees every subtree before the node above it.
+        let mut order = Vec::with_capacity(handles.len());
+        let mut stack: Vec<usize> = root.into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            order.push(idx);
+            stack.extend(slots.get(&idx).into_iter().flatten().flatten());
+        }
+        if order.len() != handles.len() {
+            return Err(TreeError::Corrupt(format!("{} of {} nodes are not reachable from the root", handles.len() - order.len(), handles.len())));
+        }
+
+        for (src, [left, right]) in &slots {
+            let parent = &handles[src];
+            let left = left.map(|left| handles[&left].clone());
+            let right = right.map(|right| handles[&right].clone());
+            for child in [&left, &right].into_iter().flatten() {
+                child.borrow_mut().parent = Some(Rc::downgrade(parent));
+            }
+            parent.borrow_mut().left = left;
+            parent.borrow_mut().right = right;
+        }
+        for idx in order.iter().rev() {
+            tree.refresh(&handles[idx]);
+        }
+        tree.root = root.map(|root| handles[&root].clone());
+        tree.len = handles.len();
+        drop(handles);
+
+        tree.validate()?;
+        Ok(tree)
+    }
+
+    /// Which of the edges leaving `src` lead to its left child: the ports say so when the edges
+    /// have them, and otherwise the first of the two edges does.
+    fn child_sides(src: usize, edges: &[ParsedEdge]) -> Result<Vec<bool>, TreeError> {
+        let sides: Option<Vec<bool>> = edges.iter().map(|edge| edge.left).collect();
+        match sides {
+            Some(sides) => {
+                let lefts = sides.iter().filter(|&&left| left).count();
+                if lefts > 1 || sides.len() - lefts > 1 {
+                    return Err(TreeError::Corrupt(format!("node {} has more than one child on one side", src)));
+                }
+                Ok(sides)
+            }
+            None if edges.iter().any(|edge| edge.left.is_some()) => {
+                Err(TreeError::Corrupt(format!("only some of the edges of node {} have ports", src)))
+            }
+            None if edges.len() == 2 => Ok(vec![true, false]),
+            None => Err(TreeError::Corrupt(format!("node {} has {} child edges instead of 2; drawings without NIL leaves need ports", src, edges.len()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::workload;
+    use crate::diff::diff;
+    use crate::dot_style::{DotStyle, NilLeaves, NodeAnnotation};
+    use crate::error::TreeError;
+    use crate::export::{Dot, Export};
+    use crate::{Dotfile, RedBlackTreeStructure};
+
+    type IntTree = RedBlackTreeStructure<i64>;
+
+    fn render(tree: &IntTree, style: DotStyle) -> String {
+        let mut file = Dotfile::with_style("unused.dot", style);
+        tree.draw_tree(&mut file);
+        Dot.render(&file)
+    }
+
+    /// A tree with red nodes at several depths and a few lone children.
+    fn sample() -> IntTree {
+        let mut tree = IntTree::new();
+        for key in workload(60, 44) {
+            tree.insert((key % 1000) as i64);
+        }
+        for key in workload(20, 44) {
+            tree.remove(&((key % 1000) as i64));
+        }
+        tree
+    }
+
+    #[test]
+    fn every_style_round_trips() {
+        let tree = sample();
+        let styles = [
+            DotStyle::default(),
+            DotStyle { nil_leaves: NilLeaves::Shared, ..DotStyle::default() },
+            DotStyle { nil_leaves: NilLeaves::Hidden, directed: true, ..DotStyle::default() },
+            DotStyle { nil_leaves: NilLeaves::Shared, directed: true, ..DotStyle::default() },
+            DotStyle { annotation: NodeAnnotation::BlackHeight, ..DotStyle::default() },
+            DotStyle { annotation: NodeAnnotation::SubtreeSize, nil_leaves: NilLeaves::Hidden, directed: true, ..DotStyle::default() },
+            DotStyle {
+                red_fill: "\"#e06666\"".to_string(),
+                black_fill: "gray20".to_string(),
+                font_color: "yellow".to_string(),
+                node_shape: Some("circle".to_string()),
+                nil_shape: Some("point".to_string()),
+                ..DotStyle::default()
+            },
+        ];
+        for style in styles {
+            let text = render(&tree, style.clone());
+            let loaded = IntTree::from_dot_str(&text).unwrap_or_else(|err| panic!("{:?}: {}", style, err));
+            assert!(diff(&tree, &loaded).is_empty(), "{:?}", style);
+            assert_eq!(loaded.len(), tree.len());
+        }
+    }
+
+    #[test]
+    fn checked_in_drawing_round_trips() {
+        let text = include_str!("../rbt.dot");
+        let tree = IntTree::from_dot_file("rbt.dot").expect("rbt.dot is a valid drawing");
+        assert_eq!(tree.inorder().collect::<Vec<_>>(), vec![1, 5, 6, 10, 15, 20, 25, 30, 43]);
+        assert_eq!(render(&tree, DotStyle::default()), text);
+    }
+
+    fn corrupt(text: &str) -> String {
+        match IntTree::from_dot_str(text) {
+            Err(TreeError::Corrupt(what)) => what,
+            other => panic!("expected a corrupt file, got {:?}", other.map(|tree| tree.len())),
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_corrupt() {
+        let cases = [
+            ("graph {\n}\ngraph {\n}\n", "second graph"),
+            ("0 [label=\"1\", color=black];\n", "expected `graph {`"),
+            ("graph {\n0 [label=\"1\", color=black]\n}\n", "missing `;`"),
+            ("digraph {\n0 [label=\"1\", color=black];\n0:n -> 1;\n}\n", "unknown port `n`"),
+            ("graph {\nx [label=\"1\", color=black];\n}\n", "node id is not a number"),
+            ("graph {\n0 [label=\"1\", color=black;\n}\n", "missing `]`"),
+            ("graph {\n0 [label=\"1\", color=black];\n0 [label=\"2\", color=black];\n}\n", "node declared twice"),
+            ("graph {\nrankdir=TB;\n}\n", "neither a node nor an edge"),
+            ("", "no `graph {` or `digraph {` found"),
+            ("graph {\n0 [color=black];\n}\n", "does not start with a label"),
+            ("graph {\n0 [label=\"1, color=black];\n}\n", "unterminated label"),
+            ("graph {\n0 [label=\"1 | 2\", color=black, shape=record];\n}\n", "record-shaped nodes"),
+            ("graph {\n0 [label=\"1\", color=blue];\n}\n", "unknown color `blue`"),
+            ("graph {\n0 [label=\"1\"];\n}\n", "node has no color"),
+            ("graph {\n0 [label=\"one\", color=black];\n}\n", "label `one` of node 0 is not a valid key"),
+            ("graph {\n0 [label=\"1\", color=black];\n5 -- 0;\n5 -- 0;\n}\n", "edge from undeclared or NIL node 5"),
+            ("graph {\n0 [label=\"1\", color=black];\n0 -- 7;\n0 -- 8;\n}\n", "edge to undeclared node 7"),
+            (
+                "graph {\n0 [label=\"2\", color=black];\n1 [label=\"1\", color=black];\n2 [label=\"3\", color=red];\n\
+                 3 [label=\"None\", color=black];\n0 -- 2;\n0 -- 3;\n1 -- 2;\n1 -- 3;\n}\n",
+                "node 2 has more than one parent",
+            ),
+            ("graph {\n0 [label=\"1\", color=black];\n1 [label=\"2\", color=black];\n}\n", "more than one root"),
+            (
+                "graph {\n0 [label=\"1\", color=black];\n1 [label=\"2\", color=black];\n2 [label=\"3\", color=black];\n\
+                 3 [label=\"None\", color=black];\n1 -- 2;\n1 -- 3;\n2 -- 1;\n2 -- 3;\n}\n",
+                "2 of 3 nodes are not reachable",
+            ),
+            (
+                "digraph {\n0 [label=\"2\", color=black];\n1 [label=\"1\", color=red];\n2 [label=\"0\", color=red];\n\
+                 0:sw -> 1;\n0:sw -> 2;\n}\n",
+                "more than one child on one side",
+            ),
+            (
+                "digraph {\n0 [label=\"2\", color=black];\n1 [label=\"1\", color=red];\n2 [label=\"3\", color=red];\n\
+                 0:sw -> 1;\n0 -> 2;\n}\n",
+                "only some of the edges of node 0 have ports",
+            ),
+            ("graph {\n0 [label=\"2\", color=black];\n1 [label=\"1\", color=red];\n0 -- 1;\n}\n", "1 child edges instead of 2"),
+        ];
+        for (text, expected) in cases {
+            let what = corrupt(text);
+            assert!(what.contains(expected), "{:?} gave {:?}", text, what);
+        }
+    }
+
+    #[test]
+    fn missing_files_are_io_errors() {
+        assert!(matches!(IntTree::from_dot_file("no such drawing.dot"), Err(TreeError::Io(_))));
+    }
+
+    #[test]
+    fn well_formed_drawings_of_broken_trees_violate_invariants() {
+        let red_root = "graph {\n0 [label=\"1\", color=red];\n}\n";
+        let red_red = "digraph {\n0 [label=\"3\", color=black];\n1 [label=\"2\", color=red];\n2 [label=\"1\", color=red];\n\
+                       0:sw -> 1;\n1:sw -> 2;\n}\n";
+        let unordered = "digraph {\n0 [label=\"1\", color=black];\n1 [label=\"2\", color=red];\n0:sw -> 1;\n}\n";
+        for text in [red_root, red_red, unordered] {
+            assert!(matches!(IntTree::from_dot_str(text), Err(TreeError::InvariantViolated(_))), "{:?}", text);
+        }
+    }
+}
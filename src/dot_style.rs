@@ -0,0 +1,98 @@
+/// What `draw_tree` does with the empty child slots of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NilLeaves {
+    /// One black "None" node per empty slot, as in the textbook pictures.
+    #[default]
+    Each,
+    /// A single "None" sentinel per drawing that every empty slot points at.
+    Shared,
+    /// No NIL nodes at all, which roughly halves the graph. Combine with `directed` so that a
+    /// lone child is still drawn on the correct side.
+    #[allow(dead_code, reason = "the demo draws NIL leaves")]
+    Hidden,
+}
+
+/// Extra information shown under the key of each node.
+#[allow(dead_code, reason = "the demo draws keys only")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NodeAnnotation {
+    #[default]
+    None,
+    /// Black nodes on a path from the node down to a NIL, the node included.
+    BlackHeight,
+    /// Nodes in the subtree rooted at the node.
+    SubtreeSize,
+}
+
+/// How a `Dotfile` renders a tree. `DotStyle::default()` gives the classic output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DotStyle {
+    pub nil_leaves: NilLeaves,
+    pub annotation: NodeAnnotation,
+    /// Graphviz colour names or `"#rrggbb"` values.
+    pub red_fill: String,
+    pub black_fill: String,
+    pub font_color: String,
+    /// Graphviz shape of key nodes and of NIL nodes; `None` leaves Graphviz's default ellipse.
+    pub node_shape: Option<String>,
+    pub nil_shape: Option<String>,
+    /// Writes a `digraph` whose edges leave the parent from its lower left or lower right corner,
+    /// so the order of the children does not depend on the layout.
+    pub directed: bool,
+}
+
+impl Default for DotStyle {
+    fn default() -> Self {
+        DotStyle {
+            nil_leaves: NilLeaves::default(),
+            annotation: NodeAnnotation::default(),
+            red_fill: "red".to_string(),
+            black_fill: "black".to_string(),
+            font_color: "white".to_string(),
+            node_shape: None,
+            nil_shape: None,
+            directed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DotStyle, NilLeaves, NodeAnnotation};
+    use crate::export::{Dot, Export};
+    use crate::{Dotfile, RedBlackTreeStructure};
+
+    fn drawing_with(style: DotStyle) -> Dotfile {
+        let tree: RedBlackTreeStructure<i32> = (1..=6).collect();
+        let mut file = Dotfile::with_style("unused.dot", style);
+        tree.draw_tree(&mut file);
+        file
+    }
+
+    fn nil_nodes(file: &Dotfile) -> usize {
+        file.nodes.iter().filter(|node| node.fields == ["None"]).count()
+    }
+
+    #[test]
+    fn nil_leaves_are_drawn_per_slot_once_or_not_at_all() {
+        // Six keys leave seven empty slots.
+        assert_eq!(nil_nodes(&drawing_with(DotStyle::default())), 7);
+        let shared = drawing_with(DotStyle { nil_leaves: NilLeaves::Shared, ..DotStyle::default() });
+        assert_eq!(nil_nodes(&shared), 1);
+        assert_eq!(shared.edges.len(), 5 + 7);
+        let hidden = drawing_with(DotStyle { nil_leaves: NilLeaves::Hidden, ..DotStyle::default() });
+        assert_eq!(nil_nodes(&hidden), 0);
+        assert_eq!(hidden.nodes.len(), 6);
+    }
+
+    #[test]
+    fn annotations_and_directed_edges() {
+        let sizes = drawing_with(DotStyle { annotation: NodeAnnotation::SubtreeSize, ..DotStyle::default() });
+        // The root stands for the whole tree.
+        assert!(sizes.nodes.iter().any(|node| node.annotation.as_deref() == Some("size=6")));
+
+        let text = Dot.render(&drawing_with(DotStyle { directed: true, ..DotStyle::default() }));
+        assert!(text.starts_with("digraph {\n"));
+        assert!(text.contains(":sw -> ") && text.contains(":se -> ") && !text.contains(" -- "));
+    }
+}
@@ -0,0 +1,160 @@
+use std::cell::Ref;
+
+use crate::error::intact;
+use crate::{Aggregate, Compare, RedBlackTree, RedBlackTreeStructure, SearchResult, Tree};
+
+/// A view into a single key of a `RedBlackTreeStructure`, obtained from a single descent by
+/// `RedBlackTreeStructure::entry`.
+///
+/// Entries bypass the tree's `DuplicatePolicy`: an occupied entry is only changed through
+/// `and_modify`/`modify`, and a vacant one always inserts a new node.
+pub enum Entry<'a, T, C, A: Aggregate<T> = ()> {
+    Occupied(OccupiedEntry<'a, T, C, A>),
+    Vacant(VacantEntry<'a, T, C, A>),
+}
+
+/// An entry whose key is stored in the tree.
+pub struct OccupiedEntry<'a, T, C, A: Aggregate<T> = ()> {
+    tree: &'a mut RedBlackTreeStructure<T, C, A>,
+    node: Tree<T, A::Value>,
+}
+
+/// An entry whose key is absent. It remembers the parent found during the descent, so
+/// inserting links the node there directly and only runs the insert fix-up.
+pub struct VacantEntry<'a, T, C, A: Aggregate<T> = ()> {
+    tree: &'a mut RedBlackTreeStructure<T, C, A>,
+    key: T,
+    parent: RedBlackTree<T, A::Value>,
+    went_left: bool,
+}
+
+#[allow(dead_code, reason = "the demo has no counts to update")]
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    pub fn entry(&mut self, key: T) -> Entry<'_, T, C, A> {
+        match self.search(&key) {
+            SearchResult::Found(node) => Entry::Occupied(OccupiedEntry { tree: self, node }),
+            SearchResult::Vacant { parent, went_left } => Entry::Vacant(VacantEntry { tree: self, key, parent, went_left }),
+        }
+    }
+}
+
+#[allow(dead_code, reason = "entry API")]
+impl<'a, T, C: Compare<T>, A: Aggregate<T>> Entry<'a, T, C, A> {
+    /// Inserts the entry's key if it is vacant.
+    pub fn or_insert(self) -> OccupiedEntry<'a, T, C, A> {
+        match self {
+            Entry::Occupied(entry) => entry,
+            Entry::Vacant(entry) => entry.insert(),
+        }
+    }
+
+    /// If vacant, stores the key built by `default` from the probe key. The built key must
+    /// compare equal to the probe, e.g. it may only fill in fields the comparator ignores.
+    pub fn or_insert_with<F: FnOnce(T) -> T>(self, default: F) -> OccupiedEntry<'a, T, C, A> {
+        match self {
+            Entry::Occupied(entry) => entry,
+            Entry::Vacant(entry) => entry.insert_with(default),
+        }
+    }
+
+    /// Runs `f` on the stored key if the entry is occupied. `f` must not change how the key
+    /// orders against the others.
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.modify(f);
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+#[allow(dead_code, reason = "entry API")]
+impl<T, C: Compare<T>, A: Aggregate<T>> OccupiedEntry<'_, T, C, A> {
+    pub fn get(&self) -> Ref<'_, T> {
+        Ref::map(self.node.borrow(), |node| node.key())
+    }
+
+    /// Runs `f` on the stored key and refreshes the aggregates above it. `f` must not change how
+    /// the key orders against the others.
+    pub fn modify<F: FnOnce(&mut T)>(&mut self, f: F) {
+        self.tree.record();
+        f(self.node.borrow_mut().key_mut());
+        self.tree.refresh_to_root(self.node.clone());
+    }
+
+    /// How many equal keys the node holds under `DuplicatePolicy::Count`.
+    pub fn count(&self) -> usize {
+        self.node.borrow().count
+    }
+
+    /// Removes the node from the tree and returns its key.
+    pub fn remove_entry(self) -> T {
+        self.tree.record();
+        intact(self.tree.take_node(self.node))
+    }
+}
+
+#[allow(dead_code, reason = "entry API")]
+impl<'a, T, C: Compare<T>, A: Aggregate<T>> VacantEntry<'a, T, C, A> {
+    pub fn key(&self) -> &T {
+        &self.key
+    }
+
+    pub fn into_key(self) -> T {
+        self.key
+    }
+
+    pub fn insert(self) -> OccupiedEntry<'a, T, C, A> {
+        self.insert_with(|key| key)
+    }
+
+    fn insert_with<F: FnOnce(T) -> T>(self, build: F) -> OccupiedEntry<'a, T, C, A> {
+        self.tree.record();
+        let node = intact(self.tree.attach(build(self.key), self.parent, self.went_left));
+        OccupiedEntry { tree: self.tree, node }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::Entry;
+    use crate::{Compare, DuplicatePolicy, RedBlackTreeStructure};
+
+    /// Orders word counts on the word alone, so the count can change in place.
+    struct ByWord;
+
+    impl Compare<(&str, u32)> for ByWord {
+        fn compare(&self, a: &(&str, u32), b: &(&str, u32)) -> Ordering {
+            a.0.cmp(b.0)
+        }
+    }
+
+    #[test]
+    fn entries_insert_once_and_modify_in_place() {
+        let mut tree = RedBlackTreeStructure::with_comparator_and_policy(ByWord, DuplicatePolicy::Reject);
+        for word in ["b", "a", "b", "c", "b"] {
+            tree.entry((word, 0)).and_modify(|entry| entry.1 += 1).or_insert_with(|(word, _)| (word, 1));
+        }
+        assert_eq!(tree.range(..), vec![("a", 1), ("b", 3), ("c", 1)]);
+
+        match tree.entry(("d", 0)) {
+            Entry::Vacant(vacant) => {
+                assert_eq!(vacant.key(), &("d", 0));
+                assert_eq!(vacant.into_key(), ("d", 0));
+            }
+            Entry::Occupied(_) => panic!("d was never inserted"),
+        }
+
+        let mut b = tree.entry(("b", 0)).or_insert();
+        assert_eq!(*b.get(), ("b", 3));
+        assert_eq!(b.count(), 1);
+        b.modify(|entry| entry.1 = 10);
+        assert_eq!(b.remove_entry(), ("b", 10));
+        assert_eq!(tree.range(..), vec![("a", 1), ("c", 1)]);
+        assert!(tree.validate().is_ok());
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::io;
+
+/// Why a fallible (`try_*`) tree operation failed.
+#[derive(Debug)]
+pub enum TreeError {
+    /// The key is not in the tree.
+    NotFound,
+    /// The key is already in the tree and the `DuplicatePolicy` rejects it.
+    Duplicate,
+    /// `validate` found a broken red-black or structural property.
+    InvariantViolated(String),
+    /// Writing or reading an export failed.
+    Io(io::Error),
+    /// An operation ran into links that a well-formed tree cannot have, e.g. a rotation about a
+    /// node without the child it needs. The tree should not be used any further.
+    Corrupt(String),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::NotFound => write!(f, "key not found"),
+            TreeError::Duplicate => write!(f, "key already present"),
+            TreeError::InvariantViolated(what) => write!(f, "invariant violated: {}", what),
+            TreeError::Io(err) => write!(f, "i/o error: {}", err),
+            TreeError::Corrupt(what) => write!(f, "corrupt tree: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TreeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TreeError {
+    fn from(err: io::Error) -> Self {
+        TreeError::Io(err)
+    }
+}
+
+/// Unwraps the result of an internal step for the infallible methods, which treat a corrupt tree
+/// as a bug; their `try_*` counterparts return the error instead.
+pub(crate) fn intact<V>(result: Result<V, TreeError>) -> V {
+    result.unwrap_or_else(|err| panic!("{}", err))
+}
@@ -0,0 +1,295 @@
+use crate::{DotNode, Dotfile, NodeColor};
+
+/// A file format a `Dotfile` drawing can be written in, see `Dotfile::write_as`.
+///
+/// Every backend keeps what `from_dot_str` needs to rebuild a tree: the red or black colour of
+/// each node even under a custom `DotStyle` fill, and which child is the left one.
+pub trait Export {
+    fn render(&self, graph: &Dotfile) -> String;
+}
+
+/// Graphviz dot, the format `Dotfile::write_file` writes.
+pub struct Dot;
+
+/// A Mermaid flowchart (`graph TD`). Child edges are labelled "L" and "R".
+pub struct Mermaid;
+
+/// GraphML, with the colour, fill and annotation of each node and the side of each child edge
+/// as data attributes.
+pub struct GraphMl;
+
+fn color_name(color: &NodeColor) -> &'static str {
+    match color {
+        NodeColor::Red => "red",
+        NodeColor::Black => "black",
+    }
+}
+
+fn side_name(left: bool) -> &'static str {
+    if left { "left" } else { "right" }
+}
+
+impl Export for Dot {
+    fn render(&self, graph: &Dotfile) -> String {
+        let mut dot_string = String::new();
+        let (kind, edge_op) = if graph.style.directed { ("digraph", "->") } else { ("graph", "--") };
+        dot_string.push_str(&format!("{} {{\n", kind));
+
+        for node in graph.nodes.iter().filter(|node| node.cluster.is_none()) {
+            dot_string.push_str(&Self::node_line(node))
+        }
+        for (cluster_idx, label) in graph.clusters.iter().enumerate() {
+            dot_string.push_str(&format!("\t subgraph cluster_{} {{\n\t label=\"{}\";\n", cluster_idx, label));
+            for node in graph.nodes.iter().filter(|node| node.cluster == Some(cluster_idx)) {
+                dot_string.push_str(&Self::node_line(node))
+            }
+            dot_string.push_str("\t }\n");
+        }
+        for edge in &graph.edges {
+            // Leave the parent from its lower left or lower right corner.
+            let port = match edge.left {
+                Some(left) if graph.style.directed => if left { ":sw" } else { ":se" },
+                _ => "",
+            };
+            let mut attrs = Vec::new();
+            if let Some(color) = &edge.emphasis {
+                attrs.push(format!("color={}, penwidth=3", color));
+            }
+            if edge.dashed {
+                attrs.push("style=dashed".to_string());
+            }
+            let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+            dot_string.push_str(&format!("\t {}{} {} {}{};\n", edge.src_id, port, edge_op, edge.dest_id, attrs))
+        }
+        dot_string.push_str("}\n");
+        dot_string
+    }
+}
+
+impl Dot {
+    fn node_line(node: &DotNode) -> String {
+        let mut label = if node.shape.as_deref() == Some("record") {
+            let escaped: Vec<String> = node.fields.iter().map(|field| Self::escape_record_field(field)).collect();
+            escaped.join(" | ")
+        } else {
+            node.fields.join(" | ")
+        };
+        if let Some(annotation) = &node.annotation {
+            label.push_str(&format!("\\n{}", annotation));
+        }
+        let shape = node.shape.as_ref().map(|shape| format!(", shape={}", shape)).unwrap_or_default();
+        // A custom fill gets a class naming the colour it stands for, so `from_dot_str` can read it back.
+        let name = color_name(&node.node_color);
+        let class = if node.color != name { format!(", class={}", name) } else { String::new() };
+        let style = if node.dashed { "\"filled,dashed\"" } else { "filled" };
+        let colors = match &node.outline {
+            Some(outline) => format!("color={}, style={}, fillcolor={}, penwidth=3", outline, style, node.color),
+            None => format!("color={}, style={}", node.color, style),
+        };
+        format!("\t {} [label=\"{}\", {}, fontcolor={}{}{}];\n", node.idx, label, colors, node.font_color, shape, class)
+    }
+
+    /// Backslash-escapes the characters that structure a record label.
+    fn escape_record_field(field: &str) -> String {
+        let mut escaped = String::with_capacity(field.len());
+        for c in field.chars() {
+            if matches!(c, '|' | '{' | '}' | '<' | '>' | '"') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
+
+impl Export for Mermaid {
+    fn render(&self, graph: &Dotfile) -> String {
+        let mut out = String::from("graph TD\n");
+        for node in graph.nodes.iter().filter(|node| node.cluster.is_none()) {
+            out.push_str(&format!("    {}\n", Self::node(node)));
+        }
+        for (cluster_idx, label) in graph.clusters.iter().enumerate() {
+            out.push_str(&format!("    subgraph c{} [\"{}\"]\n", cluster_idx, Self::text(label)));
+            for node in graph.nodes.iter().filter(|node| node.cluster == Some(cluster_idx)) {
+                out.push_str(&format!("        {}\n", Self::node(node)));
+            }
+            out.push_str("    end\n");
+        }
+        for edge in &graph.edges {
+            let link = match (graph.style.directed, edge.dashed) {
+                (true, false) => "-->",
+                (true, true) => "-.->",
+                (false, false) => "---",
+                (false, true) => "-.-",
+            };
+            let label = match edge.left {
+                Some(left) => if left { "|L|" } else { "|R|" },
+                None => "",
+            };
+            out.push_str(&format!("    n{} {}{} n{}\n", edge.src_id, link, label, edge.dest_id));
+        }
+
+        let css = |fill: &str| format!("fill:{},color:{}", fill.trim_matches('"'), graph.style.font_color.trim_matches('"'));
+        out.push_str(&format!("    classDef red {}\n", css(&graph.style.red_fill)));
+        out.push_str(&format!("    classDef black {}\n", css(&graph.style.black_fill)));
+        for node in &graph.nodes {
+            let mut styles = Vec::new();
+            if let Some(outline) = &node.outline {
+                styles.push(format!("stroke:{},stroke-width:3px", outline.trim_matches('"')));
+            }
+            if node.dashed {
+                styles.push("stroke-dasharray:5 5".to_string());
+            }
+            if !styles.is_empty() {
+                out.push_str(&format!("    style n{} {}\n", node.idx, styles.join(",")));
+            }
+        }
+        for (edge_idx, edge) in graph.edges.iter().enumerate() {
+            if let Some(color) = &edge.emphasis {
+                out.push_str(&format!("    linkStyle {} stroke:{},stroke-width:3px\n", edge_idx, color.trim_matches('"')));
+            }
+        }
+        out
+    }
+}
+
+impl Mermaid {
+    /// `n3(("15")):::red`, or a box for a record node.
+    fn node(node: &DotNode) -> String {
+        let mut label = Self::text(&node.fields.join(" | "));
+        if let Some(annotation) = &node.annotation {
+            label.push_str(&format!("<br/>{}", Self::text(annotation)));
+        }
+        let (open, close) = if node.shape.as_deref() == Some("record") { ("[", "]") } else { ("((", "))") };
+        format!("n{}{}\"{}\"{}:::{}", node.idx, open, label, close, color_name(&node.node_color))
+    }
+
+    fn text(text: &str) -> String {
+        text.replace('"', "#quot;")
+    }
+}
+
+impl Export for GraphMl {
+    fn render(&self, graph: &Dotfile) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        let keys = [
+            ("label", "node", "string"),
+            ("annotation", "node", "string"),
+            ("color", "node", "string"),
+            ("fill", "node", "string"),
+            ("outline", "node", "string"),
+            ("cluster", "node", "string"),
+            ("dashed", "all", "boolean"),
+            ("side", "edge", "string"),
+            ("emphasis", "edge", "string"),
+        ];
+        for (name, domain, kind) in keys {
+            out.push_str(&format!("  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>\n", name, domain, kind));
+        }
+        let edge_default = if graph.style.directed { "directed" } else { "undirected" };
+        out.push_str(&format!("  <graph id=\"G\" edgedefault=\"{}\">\n", edge_default));
+
+        let data = |key: &str, value: &str| format!("<data key=\"{}\">{}</data>", key, Self::escape(value));
+        for node in &graph.nodes {
+            let mut fields = vec![
+                data("label", &node.fields.join(" | ")),
+                data("color", color_name(&node.node_color)),
+                data("fill", node.color.trim_matches('"')),
+            ];
+            if let Some(annotation) = &node.annotation {
+                fields.push(data("annotation", annotation));
+            }
+            if let Some(outline) = &node.outline {
+                fields.push(data("outline", outline.trim_matches('"')));
+            }
+            if let Some(cluster) = node.cluster {
+                fields.push(data("cluster", &graph.clusters[cluster]));
+            }
+            if node.dashed {
+                fields.push(data("dashed", "true"));
+            }
+            out.push_str(&format!("    <node id=\"n{}\">{}</node>\n", node.idx, fields.concat()));
+        }
+        for edge in &graph.edges {
+            let mut fields = Vec::new();
+            if let Some(left) = edge.left {
+                fields.push(data("side", side_name(left)));
+            }
+            if let Some(color) = &edge.emphasis {
+                fields.push(data("emphasis", color.trim_matches('"')));
+            }
+            if edge.dashed {
+                fields.push(data("dashed", "true"));
+            }
+            out.push_str(&format!("    <edge source=\"n{}\" target=\"n{}\">{}</edge>\n", edge.src_id, edge.dest_id, fields.concat()));
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+impl GraphMl {
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Export, GraphMl, Mermaid};
+    use crate::dot_style::{DotStyle, NilLeaves};
+    use crate::{Dotfile, RedBlackTreeStructure};
+
+    /// A black 2 with a red 1 on its left and a red 3 on its right, drawn without NIL nodes.
+    fn drawing(directed: bool) -> Dotfile {
+        drawing_with(DotStyle { nil_leaves: NilLeaves::Hidden, directed, ..DotStyle::default() })
+    }
+
+    fn drawing_with(style: DotStyle) -> Dotfile {
+        let mut tree = RedBlackTreeStructure::new();
+        for key in [2, 1, 3] {
+            tree.insert(key);
+        }
+        let mut file = Dotfile::with_style("unused", style);
+        tree.draw_tree(&mut file);
+        file
+    }
+
+    #[test]
+    fn mermaid_keeps_colours_and_sides() {
+        let text = Mermaid.render(&drawing(true));
+        assert!(text.starts_with("graph TD\n"));
+        assert!(text.contains("n0((\"2\")):::black\n"));
+        assert!(text.contains("n1((\"1\")):::red\n"));
+        assert!(text.contains("n2((\"3\")):::red\n"));
+        assert!(text.contains("n0 -->|L| n1\n"));
+        assert!(text.contains("n0 -->|R| n2\n"));
+        assert!(text.contains("classDef red fill:red,color:white\n"));
+        assert!(Mermaid.render(&drawing(false)).contains("n0 ---|L| n1\n"));
+    }
+
+    #[test]
+    fn graphml_keeps_colours_and_sides() {
+        let text = GraphMl.render(&drawing(true));
+        assert!(text.contains("<graph id=\"G\" edgedefault=\"directed\">"));
+        assert!(text.contains("<node id=\"n1\"><data key=\"label\">1</data><data key=\"color\">red</data>"));
+        assert!(text.contains("<edge source=\"n0\" target=\"n1\"><data key=\"side\">left</data></edge>"));
+        assert!(text.contains("<edge source=\"n0\" target=\"n2\"><data key=\"side\">right</data></edge>"));
+        assert!(GraphMl.render(&drawing(false)).contains("edgedefault=\"undirected\""));
+
+        let pink = GraphMl.render(&drawing_with(DotStyle { red_fill: "\"#e06666\"".to_string(), ..DotStyle::default() }));
+        assert!(pink.contains("<data key=\"color\">red</data><data key=\"fill\">#e06666</data>"));
+    }
+
+    #[test]
+    fn graphml_escapes_labels() {
+        let mut tree = RedBlackTreeStructure::new();
+        tree.insert("a<b&c".to_string());
+        let mut file = Dotfile::new("unused");
+        tree.draw_tree(&mut file);
+        let text = GraphMl.render(&file);
+        assert!(text.contains("<data key=\"label\">&quot;a&lt;b&amp;c&quot;</data>"));
+        assert!(!text.contains("a<b"));
+    }
+}
@@ -0,0 +1,141 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use crate::{draw_node_with, Aggregate, Compare, Dotfile, RedBlackTreeStructure, Tree, TreeNode};
+
+/// What `draw_tree_with_highlights` emphasises. Nodes are named by key, and keys that are not in
+/// the tree are skipped.
+pub struct Highlights<T> {
+    /// Outlines the nodes `find_node` visits looking for this key, and the edges between them.
+    pub path_to: Option<T>,
+    pub path_color: String,
+    /// Nodes to outline, each in its own colour.
+    pub nodes: Vec<(T, String)>,
+    /// Edges to draw thick, each given by the keys of the parent and the child and a colour.
+    pub edges: Vec<(T, T, String)>,
+}
+
+impl<T> Default for Highlights<T> {
+    fn default() -> Self {
+        Highlights {
+            path_to: None,
+            path_color: "gold".to_string(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+#[allow(dead_code, reason = "the demo draws without highlights")]
+impl<T: Debug, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Draws the tree like `draw_tree`, with the nodes and edges in `highlights` emphasised. An
+    /// empty tree draws nothing.
+    pub fn draw_tree_with_highlights(&self, file: &mut Dotfile, highlights: &Highlights<T>) {
+        self.draw_highlighted(file, highlights.path_to.as_ref(), highlights);
+    }
+
+    /// Draws the tree with the nodes a lookup of `key` visits highlighted, ending at the node
+    /// holding it or, if it is missing, at the node below which it would be inserted.
+    pub fn draw_search_path(&self, file: &mut Dotfile, key: &T) {
+        self.draw_highlighted(file, Some(key), &Highlights::default());
+    }
+
+    /// `draw_tree_with_highlights` with the key of the path passed separately, so that
+    /// `draw_search_path` needs neither an owned key nor `T: Clone`.
+    fn draw_highlighted(&self, file: &mut Dotfile, path_to: Option<&T>, highlights: &Highlights<T>) {
+        if self.root.is_none() {
+            return;
+        }
+        let mut drawn = HashMap::new();
+        draw_node_with(self.root.clone(), file, None, &mut |node: &Tree<T, A::Value>, idx| {
+            drawn.insert(Rc::as_ptr(node), idx);
+        });
+        let idx_of = |node: &Tree<T, A::Value>| drawn[&Rc::as_ptr(node)];
+
+        if let Some(key) = path_to {
+            let path = self.search_path(key);
+            for node in &path {
+                file.highlight_node(idx_of(node), &highlights.path_color);
+            }
+            for step in path.windows(2) {
+                file.emphasize_edge(idx_of(&step[0]), idx_of(&step[1]), &highlights.path_color);
+            }
+        }
+        for (key, color) in &highlights.nodes {
+            if let Some(node) = self.find_node(key) {
+                file.highlight_node(idx_of(&node), color);
+            }
+        }
+        for (parent, child, color) in &highlights.edges {
+            let Some(child) = self.find_node(child) else {
+                continue;
+            };
+            if let Some(above) = TreeNode::parent_of(&child) {
+                if self.compare.compare(above.borrow().key(), parent) == Ordering::Equal {
+                    file.emphasize_edge(idx_of(&above), idx_of(&child), color);
+                }
+            }
+        }
+    }
+
+    /// The nodes `search` passes through on its way to `key`, root first.
+    fn search_path(&self, key: &T) -> Vec<Tree<T, A::Value>> {
+        let mut path = Vec::new();
+        let mut current = self.root.clone();
+        while let Some(node) = current {
+            current = match self.compare.compare(key, node.borrow().key()) {
+                Ordering::Less => node.borrow().left.clone(),
+                Ordering::Greater => node.borrow().right.clone(),
+                Ordering::Equal => None,
+            };
+            path.push(node);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Highlights;
+    use crate::{Dotfile, RedBlackTreeStructure};
+
+    fn outlined(file: &Dotfile) -> Vec<(String, String)> {
+        file.nodes.iter().filter_map(|node| Some((node.fields.join(""), node.outline.clone()?))).collect()
+    }
+
+    fn emphasized(file: &Dotfile) -> Vec<(String, String, String)> {
+        let label = |idx: usize| file.nodes[idx].fields.join("");
+        file.edges.iter().filter_map(|edge| Some((label(edge.src_id), label(edge.dest_id), edge.emphasis.clone()?))).collect()
+    }
+
+    #[test]
+    fn search_path_runs_from_the_root_to_the_key() {
+        let tree: RedBlackTreeStructure<i32> = (1..=7).collect();
+        let mut file = Dotfile::new("unused.dot");
+        tree.draw_search_path(&mut file, &5);
+        let gold = |key: &str| (key.to_string(), "gold".to_string());
+        assert_eq!(outlined(&file), vec![gold("4"), gold("6"), gold("5")]);
+        assert_eq!(emphasized(&file), vec![("4".to_string(), "6".to_string(), "gold".to_string()), ("6".to_string(), "5".to_string(), "gold".to_string())]);
+    }
+
+    #[test]
+    fn highlights_skip_keys_and_edges_not_in_the_tree() {
+        let tree: RedBlackTreeStructure<i32> = (1..=7).collect();
+        let highlights = Highlights {
+            path_to: None,
+            nodes: vec![(3, "blue".to_string()), (42, "blue".to_string())],
+            edges: vec![(2, 1, "green".to_string()), (6, 1, "green".to_string())],
+            ..Highlights::default()
+        };
+        let mut file = Dotfile::new("unused.dot");
+        tree.draw_tree_with_highlights(&mut file, &highlights);
+        assert_eq!(outlined(&file), vec![("3".to_string(), "blue".to_string())]);
+        assert_eq!(emphasized(&file), vec![("2".to_string(), "1".to_string(), "green".to_string())]);
+
+        let mut empty = Dotfile::new("unused.dot");
+        RedBlackTreeStructure::<i32>::new().draw_tree_with_highlights(&mut empty, &highlights);
+        assert!(empty.nodes.is_empty());
+    }
+}
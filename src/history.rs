@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use crate::impls::copy_tree;
+use crate::{Aggregate, RedBlackTree, RedBlackTreeStructure};
+
+/// The undo/redo journal of a `RedBlackTreeStructure`, enabled with `enable_history`.
+///
+/// Before every insert or removal that changes the tree, a deep copy of the whole tree is pushed,
+/// so undoing restores the exact previous shape and colours rather than replaying an inverse
+/// operation, which would likely rebalance differently. That makes every recorded change O(n) in
+/// time and memory instead of O(log n), see `enable_history`.
+pub(crate) struct History<T, S> {
+    depth: usize,
+    undo: VecDeque<State<T, S>>,
+    redo: Vec<State<T, S>>,
+    /// Deep-copies a tree. Stored here because it needs `T: Clone`, which the mutating methods
+    /// that call `record` do not require.
+    copy: fn(&RedBlackTree<T, S>) -> RedBlackTree<T, S>,
+}
+
+struct State<T, S> {
+    root: RedBlackTree<T, S>,
+    len: usize,
+}
+
+#[allow(dead_code, reason = "the demo keeps no history")]
+impl<T: Clone, C, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Starts journaling, keeping at most `depth` steps to undo. Restarts an existing journal.
+    ///
+    /// Each step is a full copy of the tree, so while the journal is on every change costs O(n)
+    /// to record and the journal holds up to `depth + 1` copies of the tree, counting the ones
+    /// kept for redo. Keep `depth` small for big trees, or turn the journal on only around the
+    /// edits that may need undoing.
+    pub fn enable_history(&mut self, depth: usize) {
+        self.history = Some(History { depth, undo: VecDeque::new(), redo: Vec::new(), copy: copy_tree });
+    }
+}
+
+#[allow(dead_code, reason = "the demo keeps no history")]
+impl<T, C, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Stops journaling and forgets the recorded steps.
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// Changes how many steps can be undone, dropping the oldest ones if there are more.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        if let Some(history) = &mut self.history {
+            history.depth = depth;
+            while history.undo.len() > depth {
+                history.undo.pop_front();
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().is_some_and(|history| !history.undo.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().is_some_and(|history| !history.redo.is_empty())
+    }
+
+    /// Puts the tree back the way it was before the last recorded change. Returns `false` if
+    /// there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let Some(previous) = history.undo.pop_back() else {
+            return false;
+        };
+        let current = State { root: self.root.take(), len: self.len };
+        history.redo.push(current);
+        self.restore(previous);
+        true
+    }
+
+    /// Reapplies the last undone change. Returns `false` if there is nothing to redo; any new
+    /// change clears what could be redone.
+    pub fn redo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let Some(next) = history.redo.pop() else {
+            return false;
+        };
+        let current = State { root: self.root.take(), len: self.len };
+        history.undo.push_back(current);
+        self.restore(next);
+        true
+    }
+
+    fn restore(&mut self, state: State<T, A::Value>) {
+        self.root = state.root;
+        self.len = state.len;
+        if self.tracked.is_some() {
+            self.set_audit(true);
+        }
+    }
+
+    /// Saves a copy of the current tree before a change, if journaling is on.
+    pub(crate) fn record(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.redo.clear();
+            if history.depth == 0 {
+                return;
+            }
+            if history.undo.len() == history.depth {
+                history.undo.pop_front();
+            }
+            let saved = State { root: (history.copy)(&self.root), len: self.len };
+            history.undo.push_back(saved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::workload;
+    use crate::traversal::Visitor;
+    use crate::{DuplicatePolicy, NodeColor, RedBlackTreeStructure};
+
+    /// Keys with their colours and depths in preorder, which pins down the whole shape.
+    #[derive(Default)]
+    struct Shape(Vec<(u64, NodeColor, usize)>);
+
+    impl Visitor<u64> for Shape {
+        fn enter(&mut self, key: &u64, color: NodeColor, depth: usize) {
+            self.0.push((*key, color, depth));
+        }
+    }
+
+    fn shape(tree: &RedBlackTreeStructure<u64>) -> (Vec<(u64, NodeColor, usize)>, Vec<usize>) {
+        let mut shape = Shape::default();
+        tree.visit(&mut shape);
+        let counts = shape.0.iter().map(|(key, _, _)| tree.count(key)).collect();
+        (shape.0, counts)
+    }
+
+    #[test]
+    fn undo_and_redo_restore_shapes_and_colours_exactly() {
+        let mut tree = RedBlackTreeStructure::with_policy(DuplicatePolicy::Count);
+        tree.enable_history(1000);
+        tree.set_audit(true);
+        let mut shapes = vec![shape(&tree)];
+        for step in workload(400, 43) {
+            let key = step % 60;
+            match (step >> 8) % 4 {
+                0 => drop(tree.remove(&key)),
+                1 => drop(tree.remove_one(&key)),
+                2 => drop(tree.entry(key).or_insert()),
+                _ => drop(tree.insert(key)),
+            }
+            let now = shape(&tree);
+            if shapes.last() != Some(&now) {
+                shapes.push(now);
+            }
+        }
+
+        for expected in shapes.iter().rev().skip(1) {
+            assert!(tree.undo());
+            assert_eq!(&shape(&tree), expected);
+            assert!(tree.validate().is_ok());
+            assert!(tree.audit().is_ok());
+        }
+        assert!(!tree.undo());
+        for expected in &shapes[1..] {
+            assert!(tree.redo());
+            assert_eq!(&shape(&tree), expected);
+        }
+        assert!(!tree.redo());
+    }
+
+    #[test]
+    fn depth_bounds_the_undo_steps_and_changes_clear_redo() {
+        let mut tree: RedBlackTreeStructure<u64> = RedBlackTreeStructure::new();
+        tree.enable_history(3);
+        for key in 0..10 {
+            tree.insert(key);
+        }
+        let mut undone = 0;
+        while tree.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, 3);
+        assert_eq!(tree.len(), 7);
+        assert!(tree.can_redo());
+        tree.insert(99);
+        assert!(!tree.can_redo());
+        tree.set_history_depth(1);
+        assert!(tree.undo());
+        assert!(!tree.can_undo());
+        tree.insert(100);
+        tree.disable_history();
+        assert!(!tree.undo());
+        assert_eq!(tree.count(&100), 1);
+    }
+}
@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::{Aggregate, Compare, DuplicatePolicy, RedBlackTree, RedBlackTreeStructure, Tree, TreeNode};
+
+// Equality, hashing and ordering look at the stored keys and their counts in key order, not at
+// the shape of the tree, so two trees holding the same keys compare equal however they were built.
+
+impl<T, C: Compare<T> + Default, A: Aggregate<T> + Default> Default for RedBlackTreeStructure<T, C, A> {
+    fn default() -> Self {
+        Self::from_parts(C::default(), DuplicatePolicy::default(), A::default())
+    }
+}
+
+/// Copies every node, so the clone shares nothing with the original and its parent links point
+/// into the copy. The undo history is not copied.
+impl<T: Clone, C: Clone, A: Aggregate<T> + Clone> Clone for RedBlackTreeStructure<T, C, A> {
+    fn clone(&self) -> Self {
+        let mut clone = RedBlackTreeStructure {
+            root: copy_tree(&self.root),
+            policy: self.policy,
+            len: self.len,
+            compare: self.compare.clone(),
+            aggregate: self.aggregate.clone(),
+            rotations: self.rotations,
+            recolors: self.recolors,
+            tracked: None,
+            history: None,
+        };
+        clone.set_audit(self.tracked.is_some());
+        clone
+    }
+}
+
+/// A deep copy of the tree below `root`, with parent links pointing into the copy.
+pub(crate) fn copy_tree<T: Clone, S: Clone>(root: &RedBlackTree<T, S>) -> RedBlackTree<T, S> {
+    root.as_ref().map(|root| clone_subtree(root, None))
+}
+
+fn clone_subtree<T: Clone, S: Clone>(node: &Tree<T, S>, parent: Option<&Tree<T, S>>) -> Tree<T, S> {
+    let source = node.borrow();
+    let copy = Rc::new(RefCell::new(TreeNode {
+        color: source.color.clone(),
+        key: source.key.clone(),
+        count: source.count,
+        summary: source.summary.clone(),
+        parent: parent.map(Rc::downgrade),
+        left: None,
+        right: None,
+    }));
+    let left = source.left.as_ref().map(|left| clone_subtree(left, Some(&copy)));
+    let right = source.right.as_ref().map(|right| clone_subtree(right, Some(&copy)));
+    copy.borrow_mut().left = left;
+    copy.borrow_mut().right = right;
+    copy
+}
+
+/// Builds a balanced tree in one go from the sorted keys rather than inserting them one by one.
+/// Of several equal keys the first one is kept, as repeated `insert`s would under the default
+/// `DuplicatePolicy::Reject`.
+impl<T, C: Compare<T> + Default, A: Aggregate<T> + Default> FromIterator<T> for RedBlackTreeStructure<T, C, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = Self::default();
+        let mut keys: Vec<T> = iter.into_iter().collect();
+        // The sort is stable, so equal keys stay in arrival order and `dedup_by` drops the later ones.
+        keys.sort_by(|a, b| tree.compare.compare(a, b));
+        keys.dedup_by(|later, earlier| tree.compare.compare(later, earlier) == Ordering::Equal);
+        tree.rebuild_from_sorted(keys.into_iter().map(|key| (key, 1)).collect());
+        tree
+    }
+}
+
+/// Inserts every key, resolving equal keys according to the tree's `DuplicatePolicy`.
+impl<T, C: Compare<T>, A: Aggregate<T>> Extend<T> for RedBlackTreeStructure<T, C, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for key in iter {
+            self.insert(key);
+        }
+    }
+}
+
+impl<T: PartialEq, C: Compare<T>, A: Aggregate<T>> PartialEq for RedBlackTreeStructure<T, C, A> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        let (mut mine, mut theirs) = (self.inorder(), other.inorder());
+        while let (Some(a), Some(b)) = (mine.next_node(), theirs.next_node()) {
+            let (a, b) = (a.borrow(), b.borrow());
+            if a.key() != b.key() || a.count != b.count {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<T: Eq, C: Compare<T>, A: Aggregate<T>> Eq for RedBlackTreeStructure<T, C, A> {}
+
+impl<T: Hash, C: Compare<T>, A: Aggregate<T>> Hash for RedBlackTreeStructure<T, C, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        let mut nodes = self.inorder();
+        while let Some(node) = nodes.next_node() {
+            let node = node.borrow();
+            node.key().hash(state);
+            node.count.hash(state);
+        }
+    }
+}
+
+impl<T: Ord, C: Compare<T>, A: Aggregate<T>> PartialOrd for RedBlackTreeStructure<T, C, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders trees lexicographically by their keys in tree order, compared with `Ord`, a key stored
+/// `count` times standing for that many copies.
+impl<T: Ord, C: Compare<T>, A: Aggregate<T>> Ord for RedBlackTreeStructure<T, C, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn copies<T, S>(node: &RedBlackTree<T, S>) -> usize {
+            node.as_ref().map_or(0, |node| node.borrow().count)
+        }
+
+        let (mut mine, mut theirs) = (self.inorder(), other.inorder());
+        let (mut a, mut b) = (mine.next_node(), theirs.next_node());
+        let (mut a_left, mut b_left) = (copies(&a), copies(&b));
+        loop {
+            let order = match (&a, &b) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(a), Some(b)) => a.borrow().key().cmp(b.borrow().key()),
+            };
+            if order != Ordering::Equal {
+                return order;
+            }
+            // Equal keys match copy for copy; whichever side runs out of copies moves on to its
+            // next key, which is then compared against the other side's remaining copies.
+            let matched = a_left.min(b_left);
+            a_left -= matched;
+            b_left -= matched;
+            if a_left == 0 {
+                a = mine.next_node();
+                a_left = copies(&a);
+            }
+            if b_left == 0 {
+                b = theirs.next_node();
+                b_left = copies(&b);
+            }
+        }
+    }
+}
+
+/// Shows the keys like a set, in ascending order: `{1, 5, 6}`. A key stored several times under
+/// `DuplicatePolicy::Count` is listed that many times.
+impl<T: fmt::Debug, C: Compare<T>, A: Aggregate<T>> fmt::Debug for RedBlackTreeStructure<T, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        let mut nodes = self.inorder();
+        while let Some(node) = nodes.next_node() {
+            let node = node.borrow();
+            for _ in 0..node.count {
+                set.entry(node.key());
+            }
+        }
+        set.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+
+    use crate::{DuplicatePolicy, RedBlackTree, RedBlackTreeStructure};
+
+    fn hash_of(tree: &RedBlackTreeStructure<i32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        tree.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn counted(keys: &[i32]) -> RedBlackTreeStructure<i32> {
+        let mut tree = RedBlackTreeStructure::with_policy(DuplicatePolicy::Count);
+        tree.extend(keys.iter().copied());
+        tree
+    }
+
+    /// Every node's children point back at it, all the way down.
+    fn parents_point_into(node: &RedBlackTree<i32, ()>) {
+        if let Some(node) = node {
+            for child in [&node.borrow().left, &node.borrow().right].into_iter().flatten() {
+                let parent = child.borrow().parent.as_ref().and_then(|parent| parent.upgrade());
+                assert!(parent.is_some_and(|parent| Rc::ptr_eq(&parent, node)));
+                parents_point_into(&Some(child.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn equal_keys_make_equal_trees_whatever_the_shape() {
+        let collected: RedBlackTreeStructure<i32> = (1..=10).collect();
+        let mut inserted = RedBlackTreeStructure::new();
+        inserted.extend((1..=10).rev());
+        assert_eq!(collected, inserted);
+        assert_eq!(hash_of(&collected), hash_of(&inserted));
+        inserted.insert(11);
+        assert_ne!(collected, inserted);
+        assert_ne!(counted(&[1, 2, 2]), counted(&[1, 2]));
+        assert_ne!(hash_of(&counted(&[1, 2, 2])), hash_of(&counted(&[1, 2])));
+    }
+
+    #[test]
+    fn clones_are_deep_and_linked_to_themselves() {
+        let original: RedBlackTreeStructure<i32> = (1..=20).collect();
+        let mut copy = original.clone();
+        parents_point_into(&copy.root);
+        let (a, b) = (original.root.as_ref().unwrap(), copy.root.as_ref().unwrap());
+        assert!(!Rc::ptr_eq(a, b));
+        copy.remove(&10);
+        assert_eq!(original.len(), 20);
+        assert_eq!(original.count(&10), 1);
+        assert!(copy.validate().is_ok() && original.validate().is_ok());
+    }
+
+    #[test]
+    fn trees_order_like_their_expanded_key_sequences() {
+        let tree = |keys: &[i32]| keys.iter().copied().collect::<RedBlackTreeStructure<i32>>();
+        assert_eq!(tree(&[1, 2]).cmp(&tree(&[1, 3])), Ordering::Less);
+        assert_eq!(tree(&[1, 2]).cmp(&tree(&[1, 2, 3])), Ordering::Less);
+        assert_eq!(tree(&[]).cmp(&tree(&[])), Ordering::Equal);
+        assert_eq!(counted(&[1, 2, 2]).cmp(&counted(&[1, 2, 3])), Ordering::Less);
+        assert_eq!(counted(&[1, 2, 2]).cmp(&counted(&[1, 2])), Ordering::Greater);
+        assert_eq!(counted(&[2, 2, 2]).cmp(&counted(&[2, 2, 1])), Ordering::Greater);
+        assert!(counted(&[1, 1]) < counted(&[1, 1, 1]));
+    }
+
+    #[test]
+    fn counts_follow_a_custom_comparator_when_ordering() {
+        let descending = |a: &i32, b: &i32| b.cmp(a);
+        let tree = |keys: &[i32]| {
+            let mut tree = RedBlackTreeStructure::with_comparator_and_policy(descending, DuplicatePolicy::Count);
+            tree.extend(keys.iter().copied());
+            tree
+        };
+        // In tree order these are [5, 3] and [5, 5].
+        assert_eq!(tree(&[5, 3]).cmp(&tree(&[5, 5])), Ordering::Less);
+        assert_eq!(tree(&[5, 5]).cmp(&tree(&[5, 3])), Ordering::Greater);
+        assert_eq!(tree(&[5, 5, 3]).cmp(&tree(&[5, 5])), Ordering::Greater);
+        assert_eq!(tree(&[3, 5]).cmp(&tree(&[5, 3])), Ordering::Equal);
+    }
+
+    #[test]
+    fn debug_lists_keys_like_a_set() {
+        let tree: RedBlackTreeStructure<i32> = [6, 1, 5].into_iter().collect();
+        assert_eq!(format!("{:?}", tree), "{1, 5, 6}");
+        assert_eq!(format!("{:?}", counted(&[2, 1, 2])), "{1, 2, 2}");
+        assert_eq!(format!("{:?}", RedBlackTreeStructure::<i32>::default()), "{}");
+    }
+}
@@ -0,0 +1,373 @@
+use std::cmp::Ordering;
+
+use crate::stats::TreeStats;
+use crate::error::TreeError;
+use crate::validation::check_red_black;
+use crate::{draw_node, Dotfile, NodeColor, RbNode};
+
+/// Sedgewick's left-leaning red-black tree, kept alongside `RedBlackTreeStructure` to compare the
+/// two approaches.
+///
+/// Every red node is a left child, so the tree is an isometry of a 2-3 tree and each fix-up is the
+/// same three steps on the way back up from a recursive insert or delete. Nodes are owned boxes
+/// without parent links.
+#[derive(Debug)]
+pub struct LeftLeaningRedBlackTree<T> {
+    root: Link<T>,
+    len: usize,
+    rotations: u64,
+}
+
+type Link<T> = Option<Box<LlrbNode<T>>>;
+
+#[derive(Debug)]
+struct LlrbNode<T> {
+    color: NodeColor,
+    key: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> RbNode for &LlrbNode<T> {
+    type Key = T;
+
+    fn color(&self) -> NodeColor {
+        self.color.clone()
+    }
+
+    fn left(&self) -> Option<Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<Self> {
+        self.right.as_deref()
+    }
+
+    fn with_key<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.key)
+    }
+}
+
+impl<T> Default for LeftLeaningRedBlackTree<T> {
+    fn default() -> Self {
+        LeftLeaningRedBlackTree { root: None, len: 0, rotations: 0 }
+    }
+}
+
+impl<T> LeftLeaningRedBlackTree<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(dead_code, reason = "the benchmark reports heights instead")]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code, reason = "the benchmark reports heights instead")]
+    pub fn tree_is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Total rotations performed by inserts and removes since the tree was created.
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+
+    pub fn height_of_tree(&self) -> u32 {
+        fn height<T>(link: &Link<T>) -> u32 {
+            link.as_ref().map_or(0, |node| height(&node.left).max(height(&node.right)) + 1)
+        }
+        height(&self.root)
+    }
+
+    #[allow(dead_code, reason = "the benchmark reports heights instead")]
+    pub fn stats(&self) -> TreeStats {
+        TreeStats::collect(self.root.as_deref())
+    }
+
+    fn is_red(link: &Link<T>) -> bool {
+        link.as_ref().is_some_and(|node| node.color == NodeColor::Red)
+    }
+
+    fn has_red_left(link: &Link<T>) -> bool {
+        link.as_ref().is_some_and(|node| Self::is_red(&node.left))
+    }
+
+    fn rotate_left(&mut self, mut node: Box<LlrbNode<T>>) -> Box<LlrbNode<T>> {
+        let mut right = node.right.take().expect("left-leaning invariant: rotating left needs a right child");
+        node.right = right.left.take();
+        right.color = std::mem::replace(&mut node.color, NodeColor::Red);
+        right.left = Some(node);
+        self.rotations += 1;
+        right
+    }
+
+    fn rotate_right(&mut self, mut node: Box<LlrbNode<T>>) -> Box<LlrbNode<T>> {
+        let mut left = node.left.take().expect("left-leaning invariant: rotating right needs a left child");
+        node.left = left.right.take();
+        left.color = std::mem::replace(&mut node.color, NodeColor::Red);
+        left.right = Some(node);
+        self.rotations += 1;
+        left
+    }
+
+    /// Flips a node and both its children, splitting or merging the matching 4-node.
+    fn flip_colors(node: &mut LlrbNode<T>) {
+        fn flip(color: &mut NodeColor) {
+            *color = match color {
+                NodeColor::Red => NodeColor::Black,
+                NodeColor::Black => NodeColor::Red,
+            };
+        }
+        flip(&mut node.color);
+        for child in [node.left.as_deref_mut(), node.right.as_deref_mut()].into_iter().flatten() {
+            flip(&mut child.color);
+        }
+    }
+
+    /// Restores the left-leaning shape on the way back up: lean a lone right red link left, turn
+    /// two reds in a row into a 4-node, and split 4-nodes upwards.
+    fn balance(&mut self, mut node: Box<LlrbNode<T>>) -> Box<LlrbNode<T>> {
+        if Self::is_red(&node.right) && !Self::is_red(&node.left) {
+            node = self.rotate_left(node);
+        }
+        if Self::is_red(&node.left) && Self::has_red_left(&node.left) {
+            node = self.rotate_right(node);
+        }
+        if Self::is_red(&node.left) && Self::is_red(&node.right) {
+            Self::flip_colors(&mut node);
+        }
+        node
+    }
+
+    /// Makes the left child or one of its children red before descending left.
+    fn move_red_left(&mut self, mut node: Box<LlrbNode<T>>) -> Box<LlrbNode<T>> {
+        Self::flip_colors(&mut node);
+        if node.right.as_ref().is_some_and(|right| Self::is_red(&right.left)) {
+            node.right = node.right.take().map(|right| self.rotate_right(right));
+            node = self.rotate_left(node);
+            Self::flip_colors(&mut node);
+        }
+        node
+    }
+
+    /// Makes the right child or one of its children red before descending right.
+    fn move_red_right(&mut self, mut node: Box<LlrbNode<T>>) -> Box<LlrbNode<T>> {
+        Self::flip_colors(&mut node);
+        if Self::has_red_left(&node.left) {
+            node = self.rotate_right(node);
+            Self::flip_colors(&mut node);
+        }
+        node
+    }
+
+    /// Removes the smallest key below `node`, returning the rebalanced subtree and the key.
+    fn remove_min(&mut self, mut node: Box<LlrbNode<T>>) -> (Link<T>, T) {
+        if node.left.is_none() {
+            return (None, node.key);
+        }
+        if !Self::is_red(&node.left) && !Self::has_red_left(&node.left) {
+            node = self.move_red_left(node);
+        }
+        let left = node.left.take().expect("left-leaning invariant: left child checked above");
+        let (left, min) = self.remove_min(left);
+        node.left = left;
+        (Some(self.balance(node)), min)
+    }
+
+    /// Prepares the root for a delete: with two black children it becomes the red part of a
+    /// 3-node so the descent always has a red link to borrow from.
+    fn redden_root(&mut self) {
+        if let Some(root) = self.root.as_mut() {
+            if !Self::is_red(&root.left) && !Self::is_red(&root.right) {
+                root.color = NodeColor::Red;
+            }
+        }
+    }
+
+    fn blacken_root(&mut self) {
+        if let Some(root) = self.root.as_mut() {
+            root.color = NodeColor::Black;
+        }
+    }
+
+    /// Removes and returns the smallest key.
+    #[allow(dead_code, reason = "the benchmark removes by key")]
+    pub fn remove_min_key(&mut self) -> Option<T> {
+        self.redden_root();
+        let root = self.root.take()?;
+        let (root, min) = self.remove_min(root);
+        self.root = root;
+        self.blacken_root();
+        self.len -= 1;
+        Some(min)
+    }
+
+    /// Checks the red-black properties and that every red link leans left.
+    pub fn validate(&self) -> Result<(), TreeError>
+    where
+        T: Ord,
+    {
+        check_red_black(self.root.as_deref(), T::cmp)?;
+        fn leans_left<T>(link: &Link<T>) -> bool {
+            link.as_ref().is_none_or(|node| {
+                !LeftLeaningRedBlackTree::is_red(&node.right) && leans_left(&node.left) && leans_left(&node.right)
+            })
+        }
+        if !leans_left(&self.root) {
+            return Err(TreeError::InvariantViolated("red link leans right".to_string()));
+        }
+        let nodes = self.root.as_deref().map_or(0, count_nodes);
+        if nodes != self.len {
+            return Err(TreeError::InvariantViolated(format!("len is {} but the tree holds {} nodes", self.len, nodes)));
+        }
+        Ok(())
+    }
+}
+
+fn count_nodes<T>(node: &LlrbNode<T>) -> usize {
+    node.left.as_deref().map_or(0, count_nodes) + node.right.as_deref().map_or(0, count_nodes) + 1
+}
+
+impl<T: Ord> LeftLeaningRedBlackTree<T> {
+    pub fn contains(&self, key: &T) -> bool {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match key.cmp(&node.key) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Returns `true` if the key was not present before. An equal key is left in place.
+    pub fn insert(&mut self, key: T) -> bool {
+        let mut inserted = false;
+        let root = self.root.take();
+        self.root = Some(self.insert_below(root, key, &mut inserted));
+        self.blacken_root();
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    fn insert_below(&mut self, link: Link<T>, key: T, inserted: &mut bool) -> Box<LlrbNode<T>> {
+        let Some(mut node) = link else {
+            *inserted = true;
+            return Box::new(LlrbNode { color: NodeColor::Red, key, left: None, right: None });
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => node.left = Some(self.insert_below(node.left.take(), key, inserted)),
+            Ordering::Greater => node.right = Some(self.insert_below(node.right.take(), key, inserted)),
+            Ordering::Equal => {}
+        }
+        self.balance(node)
+    }
+
+    /// Removes `key` and returns it, or `None` if it is not in the tree.
+    pub fn remove(&mut self, key: &T) -> Option<T> {
+        // The top-down descent reshapes the tree as it goes, so it must know the key is there.
+        if !self.contains(key) {
+            return None;
+        }
+        self.redden_root();
+        let root = self.root.take()?;
+        let (root, removed) = self.remove_below(root, key);
+        self.root = root;
+        self.blacken_root();
+        self.len -= 1;
+        Some(removed)
+    }
+
+    /// Removes `key`, which must be below `node`, returning the rebalanced subtree and the key.
+    fn remove_below(&mut self, mut node: Box<LlrbNode<T>>, key: &T) -> (Link<T>, T) {
+        if *key < node.key {
+            if !Self::is_red(&node.left) && !Self::has_red_left(&node.left) {
+                node = self.move_red_left(node);
+            }
+            let left = node.left.take().expect("left-leaning invariant: key is in the left subtree");
+            let (left, removed) = self.remove_below(left, key);
+            node.left = left;
+            return (Some(self.balance(node)), removed);
+        }
+        if Self::is_red(&node.left) {
+            node = self.rotate_right(node);
+        }
+        if *key == node.key && node.right.is_none() {
+            return (None, node.key);
+        }
+        if !Self::is_red(&node.right) && !Self::has_red_left(&node.right) {
+            node = self.move_red_right(node);
+        }
+        let right = node.right.take().expect("left-leaning invariant: key is in the right subtree");
+        let removed = if *key == node.key {
+            let (right, min) = self.remove_min(right);
+            node.right = right;
+            std::mem::replace(&mut node.key, min)
+        } else {
+            let (right, removed) = self.remove_below(right, key);
+            node.right = right;
+            removed
+        };
+        (Some(self.balance(node)), removed)
+    }
+}
+
+impl<T: std::fmt::Debug> LeftLeaningRedBlackTree<T> {
+    /// Draws nothing for an empty tree.
+    #[allow(dead_code, reason = "the demo draws the classic tree")]
+    pub fn draw_tree(&self, file: &mut Dotfile) {
+        if self.root.is_none() {
+            return;
+        }
+        draw_node(self.root.as_deref(), file, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::LeftLeaningRedBlackTree;
+    use crate::bench::workload;
+    use crate::Dotfile;
+
+    #[test]
+    fn random_inserts_and_removes_keep_the_tree_left_leaning() {
+        let mut tree = LeftLeaningRedBlackTree::new();
+        let mut model = BTreeSet::new();
+        for step in workload(3000, 34) {
+            let key = step % 500;
+            if (step >> 12) % 3 == 0 {
+                assert_eq!(tree.remove(&key), model.take(&key));
+            } else {
+                assert_eq!(tree.insert(key), model.insert(key));
+            }
+            assert!(tree.validate().is_ok());
+        }
+        assert_eq!(tree.len(), model.len());
+        assert_eq!(tree.stats().nodes, model.len());
+        while let Some(min) = tree.remove_min_key() {
+            assert_eq!(Some(min), model.pop_first());
+        }
+        assert!(tree.tree_is_empty() && model.is_empty());
+    }
+
+    #[test]
+    fn draws_one_node_per_key_plus_nils() {
+        let mut tree = LeftLeaningRedBlackTree::new();
+        let mut file = Dotfile::new("unused.dot");
+        tree.draw_tree(&mut file);
+        assert!(file.nodes.is_empty());
+        for key in 1..=5 {
+            tree.insert(key);
+        }
+        tree.draw_tree(&mut file);
+        assert_eq!(file.nodes.iter().filter(|node| node.fields != ["None"]).count(), 5);
+        assert_eq!(file.nodes.len(), 11);
+    }
+}
@@ -2,233 +2,846 @@ use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fs::File;
 use std::io::Write;
+use std::ops::{Bound, RangeBounds};
+use std::process::ExitCode;
 use std::rc::{Rc, Weak};
 
+use crate::dot_style::{DotStyle, NilLeaves, NodeAnnotation};
+use crate::error::{intact, TreeError};
+use crate::export::{Dot, Export};
+use crate::history::History;
+
+mod audit;
+mod bench;
+mod bulk;
+mod cli;
+#[allow(dead_code, reason = "the demo runs on one thread")]
+mod concurrent;
+mod cursor;
+mod diff;
+mod dot_import;
+mod dot_style;
+mod entry;
+mod error;
+mod export;
+mod highlight;
+mod history;
+mod impls;
+mod llrb;
+mod metrics;
+#[allow(dead_code, reason = "nothing in the demo keeps old versions yet")]
+mod persistent;
+mod stats;
+mod traversal;
+mod two_three_four;
+mod validation;
+
 #[derive(Clone, Debug, PartialEq)]
 enum NodeColor {
     Red,
     Black,
 }
 
-type Tree<T> = Rc<RefCell<TreeNode<T>>>;
-type WeakTree<T> = Weak<RefCell<TreeNode<T>>>;
-type RedBlackTree<T> = Option<Tree<T>>;
+type Tree<T, S = ()> = Rc<RefCell<TreeNode<T, S>>>;
+type WeakTree<T, S = ()> = Weak<RefCell<TreeNode<T, S>>>;
+type RedBlackTree<T, S = ()> = Option<Tree<T, S>>;
+
+
+/// What `insert` does when the key is already present in the tree.
+#[allow(dead_code, reason = "the demo inserts distinct keys under the default policy")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+enum DuplicatePolicy {
+    /// Set semantics: the existing key is kept and `insert` returns `false`.
+    #[default]
+    Reject,
+    /// The stored key is overwritten with the new one.
+    Replace,
+    /// Multiset semantics: each node keeps a count of equal keys.
+    Count,
+}
+
+/// Orders the keys of a `RedBlackTreeStructure`.
+///
+/// Implemented for `NaturalOrder` and for any `Fn(&T, &T) -> Ordering` closure, so a tree can be
+/// ordered descending, case-insensitively or by a projection without wrapping its keys.
+trait Compare<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
 
+/// The key's own `Ord` implementation; the default comparator of a tree.
+#[derive(Clone, Copy, Debug, Default)]
+struct NaturalOrder;
+
+impl<T: Ord + ?Sized> Compare<T> for NaturalOrder {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
 
-#[derive(Debug)]
-struct TreeNode<T> {
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Compare<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// A summary of the keys in a subtree, e.g. a sum, minimum, maximum or hash. Every node caches
+/// the value for its subtree, so `aggregate_range` can answer in O(log n).
+///
+/// `combine` must be associative with `identity` as its neutral element; the operands are always
+/// passed in key order, so it need not be commutative. A node holding a key `count` times under
+/// `DuplicatePolicy::Count` contributes `lift_n(key, count)`.
+trait Aggregate<T> {
+    type Value: Clone;
+
+    fn identity(&self) -> Self::Value;
+    fn lift(&self, key: &T) -> Self::Value;
+    fn combine(&self, left: &Self::Value, right: &Self::Value) -> Self::Value;
+
+    /// `lift(key)` combined with itself `count` times. The default does it by repeated doubling
+    /// in O(log count) combines; override it when there is a closed form, e.g. `key * count`.
+    fn lift_n(&self, key: &T, count: usize) -> Self::Value {
+        let mut total = self.identity();
+        let (mut power, mut count) = (self.lift(key), count);
+        while count > 0 {
+            if count & 1 == 1 {
+                total = self.combine(&total, &power);
+            }
+            count >>= 1;
+            if count > 0 {
+                power = self.combine(&power, &power);
+            }
+        }
+        total
+    }
+}
+
+/// No aggregate, for trees that don't need range summaries.
+impl<T> Aggregate<T> for () {
+    type Value = ();
+
+    fn identity(&self) {}
+    fn lift(&self, _key: &T) {}
+    fn combine(&self, _left: &(), _right: &()) {}
+}
+
+struct TreeNode<T, S = ()> {
     color: NodeColor,
-    key: T,
-    parent: Option<WeakTree<T>>,
-    left: RedBlackTree<T>,
-    right: RedBlackTree<T>,
+    /// Only `None` once the node has been removed and its key handed back, see `take_node`.
+    key: Option<T>,
+    count: usize,
+    /// The tree's `Aggregate` over this node's subtree.
+    summary: S,
+    parent: Option<WeakTree<T, S>>,
+    left: RedBlackTree<T, S>,
+    right: RedBlackTree<T, S>,
 }
 
+/// The nodes and edges of a drawing, written out by one of the `Export` backends: Graphviz dot
+/// by default, hence the name, or Mermaid or GraphML.
 struct Dotfile {
     filename: String,
+    style: DotStyle,
     nodes: Vec<DotNode>,
     edges: Vec<DotEdge>,
+    clusters: Vec<String>,
+    /// The NIL node of the current cluster under `NilLeaves::Shared`, once one is needed.
+    shared_nil: Option<usize>,
 }
 struct DotNode {
     idx: usize,
-    label: String,
+    /// The text of the node: its key, or one entry per key of a record node.
+    fields: Vec<String>,
+    /// A second line of text under the key, see `NodeAnnotation`.
+    annotation: Option<String>,
+    /// The colour the node stands for, whatever its fill.
+    node_color: NodeColor,
     color: String,
     font_color: String,
+    shape: Option<String>,
+    /// Colour of a thick border drawn around a highlighted node.
+    outline: Option<String>,
+    /// Drawn with a dashed border, for nodes that are not really there, e.g. removed in a diff.
+    dashed: bool,
+    cluster: Option<usize>,
 }
 
 struct DotEdge {
     src_id: usize,
     dest_id: usize,
+    /// `Some(true)` for the edge to a left child, `Some(false)` to a right one.
+    left: Option<bool>,
+    /// Colour of an edge drawn thick to stand out.
+    emphasis: Option<String>,
+    dashed: bool,
 }
 
 
 impl Dotfile {
     fn new(filename: &str) -> Self {
+        Self::with_style(filename, DotStyle::default())
+    }
+
+    fn with_style(filename: &str, style: DotStyle) -> Self {
         Dotfile {
             filename: filename.to_string(),
+            style,
             nodes: Vec::new(),
             edges: Vec::new(),
+            clusters: Vec::new(),
+            shared_nil: None,
         }
     }
+
+    /// Starts a labelled box: nodes added from now on are drawn inside it, until the next call.
+    fn begin_cluster(&mut self, label: &str) {
+        self.clusters.push(label.to_string());
+        self.shared_nil = None;
+    }
     fn add_node(&mut self, key: &str, node_color: NodeColor) -> usize {
+        let shape = self.style.node_shape.clone();
+        self.push_node(vec![key.to_string()], node_color, shape)
+    }
+
+    fn push_node(&mut self, fields: Vec<String>, node_color: NodeColor, shape: Option<String>) -> usize {
         let current_len = self.nodes.len();
         let color = match node_color {
-            NodeColor::Red => { "red" }
-            NodeColor::Black => { "black" }
+            NodeColor::Red => { &self.style.red_fill }
+            NodeColor::Black => { &self.style.black_fill }
         };
         self.nodes.push(DotNode {
             idx: current_len,
-            label: key.to_string(),
-            color: color.to_string(),
-            font_color: "white".to_string(),
+            fields,
+            annotation: None,
+            color: color.clone(),
+            node_color,
+            font_color: self.style.font_color.clone(),
+            shape,
+            outline: None,
+            dashed: false,
+            cluster: self.clusters.len().checked_sub(1),
         });
         current_len
     }
 
+    /// Adds a record-shaped node with one field per entry of `fields`, laid out left to right.
+    fn add_record_node(&mut self, fields: &[String]) -> usize {
+        self.push_node(fields.to_vec(), NodeColor::Black, Some("record".to_string()))
+    }
+
+    /// Draws the empty left or right child slot of `parent` as the style's `nil_leaves` asks.
+    fn add_nil(&mut self, parent: usize, left: bool) {
+        let nil_node = match (self.style.nil_leaves, self.shared_nil) {
+            (NilLeaves::Hidden, _) => return,
+            (NilLeaves::Shared, Some(shared)) => shared,
+            (nil_leaves, _) => {
+                let shape = self.style.nil_shape.clone();
+                let nil_node = self.push_node(vec!["None".to_string()], NodeColor::Black, shape);
+                if nil_leaves == NilLeaves::Shared {
+                    self.shared_nil = Some(nil_node);
+                }
+                nil_node
+            }
+        };
+        self.add_child_edge(parent, nil_node, left);
+    }
+
+    /// Adds the style's annotation under the key of a node drawn by `draw_node`.
+    fn annotate(&mut self, idx: usize, black_height: usize, size: usize) {
+        let annotation = match self.style.annotation {
+            NodeAnnotation::None => return,
+            NodeAnnotation::BlackHeight => format!("bh={}", black_height),
+            NodeAnnotation::SubtreeSize => format!("size={}", size),
+        };
+        self.nodes[idx].annotation = Some(annotation);
+    }
+
     fn add_edge(&mut self, key1: usize, key2: usize) {
         self.edges.push(DotEdge {
             src_id: key1,
             dest_id: key2,
+            left: None,
+            emphasis: None,
+            dashed: false,
         })
     }
 
-    fn write_file(&self) {
-        let mut dot_string = String::new();
-        dot_string.push_str("graph {\n");
+    fn add_child_edge(&mut self, parent: usize, child: usize, left: bool) {
+        self.edges.push(DotEdge {
+            src_id: parent,
+            dest_id: child,
+            left: Some(left),
+            emphasis: None,
+            dashed: false,
+        })
+    }
 
-        for node in &self.nodes {
-            dot_string.push_str(&format!("\t {} [label=\"{}\", color={}, style=filled, fontcolor={}];\n", node.idx, node.label, node.color, node.font_color))
-        }
-        for edge in &self.edges {
-            dot_string.push_str(&format!("\t {} -- {};\n", edge.src_id, edge.dest_id))
+    /// Draws a thick `color` border around node `idx`, keeping its fill.
+    fn highlight_node(&mut self, idx: usize, color: &str) {
+        self.nodes[idx].outline = Some(color.to_string());
+    }
+
+    /// Draws node `idx` with a dashed border.
+    fn dash_node(&mut self, idx: usize) {
+        self.nodes[idx].dashed = true;
+    }
+
+    fn add_dashed_edge(&mut self, src: usize, dest: usize) {
+        self.edges.push(DotEdge {
+            src_id: src,
+            dest_id: dest,
+            left: None,
+            emphasis: None,
+            dashed: true,
+        })
+    }
+
+    /// Draws the edges from `src` to `dest` thick and in `color`.
+    fn emphasize_edge(&mut self, src: usize, dest: usize, color: &str) {
+        for edge in self.edges.iter_mut().filter(|edge| edge.src_id == src && edge.dest_id == dest) {
+            edge.emphasis = Some(color.to_string());
         }
-        dot_string.push_str("}\n");
-        let mut dot_file = File::create(&self.filename).expect("Error while Creating file");
-        dot_file.write_all(dot_string.as_bytes()).expect("W")
+    }
+
+    #[allow(dead_code, reason = "the command line picks a format through `write_as`")]
+    fn write_file(&self) {
+        self.try_write_file().expect("Error while writing dot file")
+    }
+
+    fn try_write_file(&self) -> Result<(), TreeError> {
+        self.write_as(&Dot)
+    }
+
+    /// Writes the drawing to `filename` in the given format.
+    fn write_as(&self, format: &impl Export) -> Result<(), TreeError> {
+        let mut file = File::create(&self.filename)?;
+        file.write_all(format.render(self).as_bytes())?;
+        Ok(())
+    }
+}
+
+// Written out by hand so the key prints as itself rather than as `Some(key)`.
+impl<T: std::fmt::Debug, S: std::fmt::Debug> std::fmt::Debug for TreeNode<T, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut node = f.debug_struct("TreeNode");
+        node.field("color", &self.color);
+        match &self.key {
+            Some(key) => node.field("key", key),
+            None => node.field("key", &self.key),
+        };
+        node.field("count", &self.count)
+            .field("summary", &self.summary)
+            .field("parent", &self.parent)
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .finish()
     }
 }
 
-impl<T: Ord + Clone + std::fmt::Debug> TreeNode<T> {
-    pub fn new(key: T) -> Tree<T> {
+impl<T, S> TreeNode<T, S> {
+    pub fn new(key: T, summary: S) -> Tree<T, S> {
         Rc::new(RefCell::new(TreeNode {
             color: NodeColor::Red,
-            key,
+            key: Some(key),
+            count: 1,
+            summary,
             parent: None,
             left: None,
             right: None,
         }))
     }
 
-    fn draw_node(node: &RedBlackTree<T>, file: &mut Dotfile, mut parent_node_idx: Option<usize>) {
-        if let Some(root) = node {
-            let root_node = match parent_node_idx {
-                None => { file.add_node(format!("{:?}", root.clone().borrow().key.clone()).as_str(), root.clone().borrow().color.clone()) }
-                Some(parent_val) => {
-                    parent_val
-                }
-            };
+    fn key(&self) -> &T {
+        self.key.as_ref().expect("only removed nodes give up their key")
+    }
 
-            if let Some(left) = root.clone().borrow().left.clone() {
-                let left_node = file.add_node(format!("{:?}", left.borrow().key.clone()).as_str(), left.borrow().color.clone());
-                file.add_edge(root_node, left_node);
-                Self::draw_node(&root.clone().borrow().left.clone(), file, Some(left_node));
-            } else {
-                let left_node = file.add_node("None", NodeColor::Black);
-                file.add_edge(root_node, left_node);
+    fn key_mut(&mut self) -> &mut T {
+        self.key.as_mut().expect("only removed nodes give up their key")
+    }
+
+    fn parent_of(node: &Tree<T, S>) -> RedBlackTree<T, S> {
+        node.borrow().parent.as_ref().and_then(|p| p.upgrade())
+    }
+
+    /// NIL children count as black.
+    fn is_red(node: &RedBlackTree<T, S>) -> bool {
+        node.as_ref().is_some_and(|node| node.borrow().color == NodeColor::Red)
+    }
+
+    /// The next node in key order, found through the right subtree or the parent links.
+    fn successor(node: &Tree<T, S>) -> RedBlackTree<T, S> {
+        if let Some(right) = node.borrow().right.clone() {
+            let mut current = right;
+            while let Some(left) = current.clone().borrow().left.clone() {
+                current = left;
             }
-            if let Some(right) = root.clone().borrow().right.clone() {
-                let right_node = file.add_node(format!("{:?}", right.borrow().key.clone()).as_str(), right.borrow().color.clone());
-                file.add_edge(root_node, right_node);
-                Self::draw_node(&root.clone().borrow().right.clone(), file, Some(right_node));
-            } else {
-                let right_node = file.add_node("None", NodeColor::Black);
-                file.add_edge(root_node, right_node);
+            return Some(current);
+        }
+        let mut child = node.clone();
+        while let Some(parent) = Self::parent_of(&child) {
+            if parent.borrow().left.as_ref().is_some_and(|left| Rc::ptr_eq(left, &child)) {
+                return Some(parent);
             }
+            child = parent;
         }
+        None
+    }
+
+    /// The previous node in key order; the mirror image of `successor`.
+    fn predecessor(node: &Tree<T, S>) -> RedBlackTree<T, S> {
+        if let Some(left) = node.borrow().left.clone() {
+            let mut current = left;
+            while let Some(right) = current.clone().borrow().right.clone() {
+                current = right;
+            }
+            return Some(current);
+        }
+        let mut child = node.clone();
+        while let Some(parent) = Self::parent_of(&child) {
+            if parent.borrow().right.as_ref().is_some_and(|right| Rc::ptr_eq(right, &child)) {
+                return Some(parent);
+            }
+            child = parent;
+        }
+        None
+    }
+}
+
+/// Read access to a node of any of the red-black tree variants, so the `Dotfile` export and the
+/// checks in `validation` work on all of them.
+trait RbNode: Clone {
+    type Key;
+
+    fn color(&self) -> NodeColor;
+    fn left(&self) -> Option<Self>;
+    fn right(&self) -> Option<Self>;
+    fn with_key<R>(&self, f: impl FnOnce(&Self::Key) -> R) -> R;
+}
+
+impl<T, S> RbNode for Tree<T, S> {
+    type Key = T;
+
+    fn color(&self) -> NodeColor {
+        self.borrow().color.clone()
+    }
+
+    fn left(&self) -> Option<Self> {
+        self.borrow().left.clone()
+    }
+
+    fn right(&self) -> Option<Self> {
+        self.borrow().right.clone()
+    }
+
+    fn with_key<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(self.borrow().key())
     }
 }
-#[derive(Debug)]
-struct RedBlackTreeStructure<T> {
-    root: RedBlackTree<T>,
+
+fn draw_node<N: RbNode>(node: Option<N>, file: &mut Dotfile, parent_node_idx: Option<usize>)
+where
+    N::Key: std::fmt::Debug,
+{
+    draw_node_with(node, file, parent_node_idx, &mut |_, _| {})
 }
 
-impl<T: Ord + std::fmt::Debug + std::fmt::Display + std::clone::Clone> RedBlackTreeStructure<T> {
+/// Like `draw_node`, also handing every key node and the index it was drawn as to `on_node`.
+fn draw_node_with<N: RbNode>(node: Option<N>, file: &mut Dotfile, parent_node_idx: Option<usize>, on_node: &mut dyn FnMut(&N, usize))
+where
+    N::Key: std::fmt::Debug,
+{
+    if let Some(root) = node {
+        let root_node = match parent_node_idx {
+            None => { root.with_key(|key| file.add_node(format!("{:?}", key).as_str(), root.color())) }
+            Some(parent_val) => {
+                parent_val
+            }
+        };
+        on_node(&root, root_node);
+        draw_children(&root, root_node, file, on_node);
+    }
+}
+
+/// Draws the children of `node`, already drawn as `node_idx`, and everything below them. Returns
+/// the black height and size of the subtree for `Dotfile::annotate`.
+fn draw_children<N: RbNode>(node: &N, node_idx: usize, file: &mut Dotfile, on_node: &mut dyn FnMut(&N, usize)) -> (usize, usize)
+where
+    N::Key: std::fmt::Debug,
+{
+    let mut black_height = 0;
+    let mut size = 1;
+    for (child, left) in [(node.left(), true), (node.right(), false)] {
+        match child {
+            Some(child) => {
+                let child_node = child.with_key(|key| file.add_node(format!("{:?}", key).as_str(), child.color()));
+                file.add_child_edge(node_idx, child_node, left);
+                on_node(&child, child_node);
+                let (child_height, child_size) = draw_children(&child, child_node, file, on_node);
+                black_height = black_height.max(child_height);
+                size += child_size;
+            }
+            None => file.add_nil(node_idx, left),
+        }
+    }
+    black_height += usize::from(node.color() == NodeColor::Black);
+    file.annotate(node_idx, black_height, size);
+    (black_height, size)
+}
+
+/// Where a descent for a key ended: at the node holding an equal key, or at the empty child slot
+/// of `parent` (the root slot when `parent` is `None`) where it would be attached.
+enum SearchResult<T, S> {
+    Found(Tree<T, S>),
+    Vacant { parent: RedBlackTree<T, S>, went_left: bool },
+}
+
+struct RedBlackTreeStructure<T, C = NaturalOrder, A: Aggregate<T> = ()> {
+    root: RedBlackTree<T, A::Value>,
+    policy: DuplicatePolicy,
+    len: usize,
+    compare: C,
+    aggregate: A,
+    rotations: u64,
+    recolors: u64,
+    /// Every node created since `set_audit(true)`, when auditing is on. See `audit`.
+    tracked: Option<Vec<WeakTree<T, A::Value>>>,
+    history: Option<History<T, A::Value>>,
+}
+
+impl<T: Ord> RedBlackTreeStructure<T> {
     pub fn new() -> Self {
-        Self { root: None }
+        Self::with_policy(DuplicatePolicy::default())
     }
 
-    pub fn insert(&mut self, key: T)
-    where
-        T: Clone + Ord,
-    {
-        let new_node = TreeNode::new(key.clone());
+    pub fn with_policy(policy: DuplicatePolicy) -> Self {
+        Self::with_comparator_and_policy(NaturalOrder, policy)
+    }
+}
 
-        if self.root.is_none() {
-            new_node.borrow_mut().color = NodeColor::Black;
-            self.root = Some(new_node);
-        } else {
-            let mut current = self.root.clone();
-            let mut parent = None;
-
-            while let Some(node) = current {
-                let cmp = key.cmp(&node.borrow().key);
-                parent = Some(node.clone());
-                current = match cmp {
-                    Ordering::Less => node.borrow().left.clone(),
-                    Ordering::Greater | Ordering::Equal => node.borrow().right.clone(),
-                };
+impl<T> RedBlackTreeStructure<T> {
+    fn number_of_leaves<S>(root: &RedBlackTree<T, S>) -> u32 {
+        if root.is_none() {
+            return 0;
+        }
+        if root.clone().unwrap().borrow().left.is_none() && root.clone().unwrap().borrow().right.is_none() {
+            return 1;
+        }
+        return RedBlackTreeStructure::<T>::number_of_leaves(&root.clone().unwrap().borrow().left.clone()) + RedBlackTreeStructure::<T>::number_of_leaves(&root.clone().unwrap().borrow().right.clone());
+    }
+
+    fn height_of_tree<S>(root: &RedBlackTree<T, S>) -> u32 {
+        if root.is_none() {
+            return 0;
+        }
+
+        let left_height = Self::height_of_tree(&root.clone().unwrap().borrow().left.clone());
+        let right_height = Self::height_of_tree(&root.clone().unwrap().borrow().right.clone());
+        std::cmp::max(left_height, right_height) + 1
+    }
+}
+
+impl<T: std::fmt::Display> RedBlackTreeStructure<T> {
+    fn in_order_traversal<S>(root: &RedBlackTree<T, S>) {
+        if root.is_some() {
+            Self::in_order_traversal(&root.clone().unwrap().borrow().left.clone());
+            println!("{}", root.clone().unwrap().borrow().key());
+            Self::in_order_traversal(&root.clone().unwrap().borrow().right.clone());
+        }
+    }
+}
+
+impl<T: Ord, A: Aggregate<T>> RedBlackTreeStructure<T, NaturalOrder, A> {
+    /// Creates an empty tree that keeps `aggregate` up to date for every subtree.
+    #[allow(dead_code, reason = "the demo keeps no summaries")]
+    pub fn with_aggregate(aggregate: A) -> Self {
+        Self::from_parts(NaturalOrder, DuplicatePolicy::default(), aggregate)
+    }
+}
+
+impl<T, C: Compare<T>> RedBlackTreeStructure<T, C> {
+    /// Creates an empty tree ordered by `compare` instead of `T`'s `Ord`.
+    #[allow(dead_code, reason = "the demo orders its keys naturally")]
+    pub fn with_comparator(compare: C) -> Self {
+        Self::with_comparator_and_policy(compare, DuplicatePolicy::default())
+    }
+
+    pub fn with_comparator_and_policy(compare: C, policy: DuplicatePolicy) -> Self {
+        Self::from_parts(compare, policy, ())
+    }
+}
+
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    pub fn from_parts(compare: C, policy: DuplicatePolicy, aggregate: A) -> Self {
+        Self { root: None, policy, len: 0, compare, aggregate, rotations: 0, recolors: 0, tracked: None, history: None }
+    }
+
+    #[allow(dead_code, reason = "accessor for callers of `with_policy`")]
+    pub fn policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    /// Number of nodes, i.e. distinct keys, in the tree.
+    #[allow(dead_code, reason = "the demo reports leaves and height instead")]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Total rotations performed by inserts and deletes since the tree was created.
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+
+    /// Total colour flips made while rebalancing since the tree was created. Colouring a new
+    /// node red, or the first root black, does not count.
+    #[allow(dead_code, reason = "the metrics runner reads the field directly")]
+    pub fn recolors(&self) -> u64 {
+        self.recolors
+    }
+
+    /// Inserts `key`, resolving an equal key according to the tree's `DuplicatePolicy`.
+    /// Returns `true` if the key was not present before.
+    pub fn insert(&mut self, key: T) -> bool {
+        intact(self.insert_key(key))
+    }
+
+    /// Like `insert`, but fails with `TreeError::Duplicate` when the policy rejects an equal key,
+    /// and with `TreeError::Corrupt` instead of panicking when rebalancing meets a broken link.
+    #[allow(dead_code, reason = "the demo uses the infallible insert")]
+    pub fn try_insert(&mut self, key: T) -> Result<(), TreeError> {
+        match self.insert_key(key)? {
+            false if self.policy == DuplicatePolicy::Reject => Err(TreeError::Duplicate),
+            _ => Ok(()),
+        }
+    }
+
+    fn insert_key(&mut self, key: T) -> Result<bool, TreeError> {
+        match self.search(&key) {
+            SearchResult::Found(node) => {
+                if self.policy != DuplicatePolicy::Reject {
+                    self.record();
+                }
+                let mut existing = node.borrow_mut();
+                match self.policy {
+                    DuplicatePolicy::Reject => {}
+                    DuplicatePolicy::Replace => {
+                        existing.key = Some(key);
+                        drop(existing);
+                        self.refresh_to_root(node);
+                    }
+                    DuplicatePolicy::Count => {
+                        existing.count += 1;
+                        drop(existing);
+                        self.refresh_to_root(node);
+                    }
+                }
+                Ok(false)
             }
+            SearchResult::Vacant { parent, went_left } => {
+                self.record();
+                self.attach(key, parent, went_left)?;
+                Ok(true)
+            }
+        }
+    }
 
-            if let Some(parent_node) = parent {
+    /// Walks down from the root to the node holding `key`, or to the empty slot it belongs in.
+    fn search(&self, key: &T) -> SearchResult<T, A::Value> {
+        let mut current = self.root.clone();
+        let mut parent = None;
+        let mut went_left = false;
+
+        while let Some(node) = current {
+            let cmp = self.compare.compare(key, node.borrow().key());
+            current = match cmp {
+                Ordering::Less => node.borrow().left.clone(),
+                Ordering::Greater => node.borrow().right.clone(),
+                Ordering::Equal => return SearchResult::Found(node),
+            };
+            went_left = cmp == Ordering::Less;
+            parent = Some(node);
+        }
+        SearchResult::Vacant { parent, went_left }
+    }
+
+    /// Links a new node for `key` into the slot found by `search` and rebalances.
+    fn attach(&mut self, key: T, parent: RedBlackTree<T, A::Value>, went_left: bool) -> Result<Tree<T, A::Value>, TreeError> {
+        let summary = self.aggregate.lift(&key);
+        let new_node = TreeNode::new(key, summary);
+        self.track(&new_node);
+        self.len += 1;
+        match parent {
+            None => {
+                new_node.borrow_mut().color = NodeColor::Black;
+                self.root = Some(new_node.clone());
+            }
+            Some(parent_node) => {
                 new_node.borrow_mut().parent = Some(Rc::downgrade(&parent_node));
-                if key < parent_node.borrow().key {
+                if went_left {
                     parent_node.borrow_mut().left = Some(new_node.clone());
                 } else {
                     parent_node.borrow_mut().right = Some(new_node.clone());
                 }
+                self.refresh_to_root(parent_node);
+                self.fix_insert(new_node.clone())?;
             }
+        }
+        self.audit_if_enabled()?;
+        Ok(new_node)
+    }
+
+    /// Deletes `node` and hands back its key. The key is taken out of its slot, so a handle to
+    /// the node kept elsewhere, e.g. by a leaked iterator, is left holding an empty node.
+    fn take_node(&mut self, node: Tree<T, A::Value>) -> Result<T, TreeError> {
+        self.delete_node(node.clone())?;
+        let key = node.borrow_mut().key.take().ok_or_else(|| TreeError::Corrupt("node was removed twice".to_string()))?;
+        // The audit counts live nodes, and this handle is the last one to the removed node.
+        drop(node);
+        self.audit_if_enabled()?;
+        Ok(key)
+    }
+
+    /// How many times `key` is stored. Outside of `DuplicatePolicy::Count` this is 0 or 1.
+    #[allow(dead_code, reason = "multiset API, unused by the demo")]
+    pub fn count(&self, key: &T) -> usize {
+        self.find_node(key).map_or(0, |node| node.borrow().count)
+    }
 
-            self.fix_insert(new_node);
+    /// Removes the node holding `key`, whatever its count, and returns the key it stored. Returns
+    /// `None` if the key was not present.
+    pub fn remove(&mut self, key: &T) -> Option<T> {
+        match self.try_remove(key) {
+            Ok(key) => Some(key),
+            Err(TreeError::NotFound) => None,
+            Err(err) => panic!("{}", err),
         }
     }
 
-    fn fix_insert(&mut self, mut node: Tree<T>) {
-        while let Some(parent) = node.clone().borrow().parent.clone().and_then(|p| p.upgrade()) {
+    /// Like `remove`, but reports an absent key as `TreeError::NotFound` and a broken link met
+    /// while rebalancing as `TreeError::Corrupt`.
+    pub fn try_remove(&mut self, key: &T) -> Result<T, TreeError> {
+        let node = self.find_node(key).ok_or(TreeError::NotFound)?;
+        self.record();
+        self.take_node(node)
+    }
+
+    /// Same as `remove`, under the name `BTreeSet` uses for handing back the stored value.
+    #[allow(dead_code, reason = "the demo deletes by key")]
+    pub fn take(&mut self, key: &T) -> Option<T> {
+        self.remove(key)
+    }
+
+    /// Removes a single occurrence of `key`, dropping the node once its count reaches zero.
+    /// Returns `false` if the key was not present.
+    #[allow(dead_code, reason = "multiset API, unused by the demo")]
+    pub fn remove_one(&mut self, key: &T) -> bool {
+        match self.try_remove_one(key) {
+            Ok(()) => true,
+            Err(TreeError::NotFound) => false,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `remove_one`, but reports an absent key as `TreeError::NotFound` and a broken link
+    /// met while rebalancing as `TreeError::Corrupt`.
+    pub fn try_remove_one(&mut self, key: &T) -> Result<(), TreeError> {
+        let node = self.find_node(key).ok_or(TreeError::NotFound)?;
+        self.record();
+        if node.borrow().count > 1 {
+            node.borrow_mut().count -= 1;
+            self.refresh_to_root(node);
+            return Ok(());
+        }
+        self.delete_node(node)?;
+        self.audit_if_enabled()
+    }
+
+    /// Removes every occurrence of `key` and returns how many there were.
+    #[allow(dead_code, reason = "multiset API, unused by the demo")]
+    pub fn remove_all(&mut self, key: &T) -> usize {
+        match self.try_remove_all(key) {
+            Ok(count) => count,
+            Err(TreeError::NotFound) => 0,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `remove_all`, but reports an absent key as `TreeError::NotFound` and a broken link
+    /// met while rebalancing as `TreeError::Corrupt`.
+    pub fn try_remove_all(&mut self, key: &T) -> Result<usize, TreeError> {
+        let node = self.find_node(key).ok_or(TreeError::NotFound)?;
+        self.record();
+        let count = node.borrow().count;
+        self.delete_node(node)?;
+        self.audit_if_enabled()?;
+        Ok(count)
+    }
+
+    fn fix_insert(&mut self, mut node: Tree<T, A::Value>) -> Result<(), TreeError> {
+        while let Some(parent) = TreeNode::parent_of(&node) {
             if parent.borrow().color != NodeColor::Red {
                 break;
             }
 
-            let grandparent = parent.borrow().parent.clone().and_then(|gp| gp.upgrade());
-            if let Some(grandparent_node) = grandparent {
-                let is_left = match grandparent_node.borrow().left.as_ref() {
-                    Some(left) if Rc::ptr_eq(&parent, left) => true,
-                    _ => false,
-                };
+            // A red parent is never the root, so the grandparent exists
+            let Some(grandparent_node) = TreeNode::parent_of(&parent) else {
+                break;
+            };
+            let is_left = matches!(grandparent_node.borrow().left.as_ref(), Some(left) if Rc::ptr_eq(&parent, left));
 
-                let uncle = if is_left {
-                    grandparent_node.borrow().right.clone()
-                } else {
-                    grandparent_node.borrow().left.clone()
-                };
+            let uncle = if is_left {
+                grandparent_node.borrow().right.clone()
+            } else {
+                grandparent_node.borrow().left.clone()
+            };
 
-                if let Some(uncle_node) = uncle {
-                    if uncle_node.borrow().color == NodeColor::Red {
-                        // Recolor
-                        parent.borrow_mut().color = NodeColor::Black;
-                        uncle_node.borrow_mut().color = NodeColor::Black;
-                        grandparent_node.borrow_mut().color = NodeColor::Red;
-                        drop(node);
-                        node = grandparent_node.clone();
-                        continue;
-                    }
+            if let Some(uncle_node) = uncle {
+                if uncle_node.borrow().color == NodeColor::Red {
+                    // Recolor
+                    self.recolor(&parent, NodeColor::Black);
+                    self.recolor(&uncle_node, NodeColor::Black);
+                    self.recolor(&grandparent_node, NodeColor::Red);
+                    node = grandparent_node;
+                    continue;
                 }
+            }
 
-                if is_left {
-                    if Rc::ptr_eq(&node, &parent.borrow().right.as_ref().unwrap()) {
-                        self.rotate_left(parent.clone());
-                        node = parent.clone();
-                    }
-                    self.rotate_right(grandparent_node.clone());
-                } else {
-                    if parent.borrow().left.as_ref().is_some() && Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap()) {
-                        self.rotate_right(parent.clone());
-                        node = parent.clone();
-                    }
-                    self.rotate_left(grandparent_node.clone());
+            // Rotate an inner grandchild to the outside first, so `node` is the outer one
+            let mut parent = parent;
+            if is_left {
+                if parent.borrow().right.as_ref().is_some_and(|right| Rc::ptr_eq(&node, right)) {
+                    self.rotate_left(parent.clone())?;
+                    std::mem::swap(&mut node, &mut parent);
                 }
-
-                parent.borrow_mut().color = NodeColor::Black;
-                grandparent_node.borrow_mut().color = NodeColor::Red;
+                self.rotate_right(grandparent_node.clone())?;
+            } else {
+                if parent.borrow().left.as_ref().is_some_and(|left| Rc::ptr_eq(&node, left)) {
+                    self.rotate_right(parent.clone())?;
+                    std::mem::swap(&mut node, &mut parent);
+                }
+                self.rotate_left(grandparent_node.clone())?;
             }
+
+            self.recolor(&parent, NodeColor::Black);
+            self.recolor(&grandparent_node, NodeColor::Red);
+            break;
+        }
+
+        if let Some(root) = self.root.clone() {
+            self.recolor(&root, NodeColor::Black);
         }
+        Ok(())
+    }
 
-        if let Some(root) = &self.root {
-            root.borrow_mut().color = NodeColor::Black;
+    /// Sets the colour of `node`, counting it in `recolors` if it changes.
+    fn recolor(&mut self, node: &Tree<T, A::Value>, color: NodeColor) {
+        let mut node = node.borrow_mut();
+        if node.color != color {
+            node.color = color;
+            self.recolors += 1;
         }
     }
 
-    fn rotate_left(&mut self, node: Tree<T>) {
-        let right = node.borrow_mut().right.take().unwrap();
+    fn rotate_left(&mut self, node: Tree<T, A::Value>) -> Result<(), TreeError> {
+        let right = node.borrow_mut().right.take().ok_or_else(|| TreeError::Corrupt("rotate_left about a node without a right child".to_string()))?;
         node.borrow_mut().right = right.borrow_mut().left.take();
 
         if let Some(left_child) = &node.borrow().right {
@@ -239,7 +852,7 @@ impl<T: Ord + std::fmt::Debug + std::fmt::Display + std::clone::Clone> RedBlackT
         right.borrow_mut().parent = parent.clone();
 
         if let Some(parent_node) = parent.and_then(|p| p.upgrade()) {
-            if Rc::ptr_eq(&node, &parent_node.borrow().left.as_ref().unwrap()) {
+            if parent_node.borrow().left.as_ref().is_some_and(|child| Rc::ptr_eq(&node, child)) {
                 parent_node.borrow_mut().left = Some(right.clone());
             } else {
                 parent_node.borrow_mut().right = Some(right.clone());
@@ -250,10 +863,14 @@ impl<T: Ord + std::fmt::Debug + std::fmt::Display + std::clone::Clone> RedBlackT
 
         right.borrow_mut().left = Some(node.clone());
         node.borrow_mut().parent = Some(Rc::downgrade(&right));
+        self.refresh(&node);
+        self.refresh(&right);
+        self.rotations += 1;
+        Ok(())
     }
 
-    fn rotate_right(&mut self, node: Tree<T>) {
-        let left = node.borrow_mut().left.take().unwrap();
+    fn rotate_right(&mut self, node: Tree<T, A::Value>) -> Result<(), TreeError> {
+        let left = node.borrow_mut().left.take().ok_or_else(|| TreeError::Corrupt("rotate_right about a node without a left child".to_string()))?;
         node.borrow_mut().left = left.borrow_mut().right.take();
 
         if let Some(right_child) = &node.borrow().left {
@@ -264,7 +881,7 @@ impl<T: Ord + std::fmt::Debug + std::fmt::Display + std::clone::Clone> RedBlackT
         left.borrow_mut().parent = parent.clone();
 
         if let Some(parent_node) = parent.and_then(|p| p.upgrade()) {
-            if Rc::ptr_eq(&node, &parent_node.borrow().left.as_ref().unwrap()) {
+            if parent_node.borrow().left.as_ref().is_some_and(|child| Rc::ptr_eq(&node, child)) {
                 parent_node.borrow_mut().left = Some(left.clone());
             } else {
                 parent_node.borrow_mut().right = Some(left.clone());
@@ -275,435 +892,590 @@ impl<T: Ord + std::fmt::Debug + std::fmt::Display + std::clone::Clone> RedBlackT
 
         left.borrow_mut().right = Some(node.clone());
         node.borrow_mut().parent = Some(Rc::downgrade(&left));
+        self.refresh(&node);
+        self.refresh(&left);
+        self.rotations += 1;
+        Ok(())
     }
-    fn number_of_leaves(root: &RedBlackTree<T>) -> u32 {
-        if root.is_none() {
-            return 0;
-        }
-        if root.clone().unwrap().borrow().left.is_none() && root.clone().unwrap().borrow().left.is_none() {
-            return 1;
+    fn tree_is_empty(&self) -> bool {
+        if self.root.is_none() {
+            return true;
         }
-        return RedBlackTreeStructure::<T>::number_of_leaves(&root.clone().unwrap().borrow().left.clone()) + RedBlackTreeStructure::<T>::number_of_leaves(&root.clone().unwrap().borrow().right.clone());
+        false
     }
 
-    fn height_of_tree(root: &RedBlackTree<T>) -> u32 {
-        if root.is_none() {
-            return 0;
+    fn find_node(&self, key: &T) -> RedBlackTree<T, A::Value> {
+        match self.search(key) {
+            SearchResult::Found(node) => Some(node),
+            SearchResult::Vacant { .. } => None,
         }
+    }
 
-        let left_height = Self::height_of_tree(&root.clone().unwrap().borrow().left.clone());
-        let right_height = Self::height_of_tree(&root.clone().unwrap().borrow().right.clone());
-        std::cmp::max(left_height, right_height) + 1
+    /// Recomputes `node`'s summary from its key and its children's summaries.
+    fn refresh(&self, node: &Tree<T, A::Value>) {
+        let summary = {
+            let node = node.borrow();
+            let left = node.left.as_ref().map_or_else(|| self.aggregate.identity(), |left| left.borrow().summary.clone());
+            let right = node.right.as_ref().map_or_else(|| self.aggregate.identity(), |right| right.borrow().summary.clone());
+            let own = self.aggregate.lift_n(node.key(), node.count);
+            self.aggregate.combine(&self.aggregate.combine(&left, &own), &right)
+        };
+        node.borrow_mut().summary = summary;
     }
 
-    fn in_order_traversal(root: &RedBlackTree<T>) {
-        if root.is_some() {
-            Self::in_order_traversal(&root.clone().unwrap().borrow().left.clone());
-            println!("{}", root.clone().unwrap().borrow().key);
-            Self::in_order_traversal(&root.clone().unwrap().borrow().right.clone());
+    /// Refreshes the summaries from `node` up to the root, after the subtree below it changed.
+    fn refresh_to_root(&self, node: Tree<T, A::Value>) {
+        let mut current = Some(node);
+        while let Some(node) = current {
+            self.refresh(&node);
+            current = TreeNode::parent_of(&node);
         }
     }
 
-    fn tree_is_empty(&self) -> bool {
-        if self.root.is_none() {
-            return true;
+    /// The aggregate over all keys in the tree.
+    #[allow(dead_code, reason = "the demo keeps no summaries")]
+    pub fn aggregate(&self) -> A::Value {
+        self.root.as_ref().map_or_else(|| self.aggregate.identity(), |root| root.borrow().summary.clone())
+    }
+
+    /// The aggregate over the keys within `range`, combined in key order. Only the two boundary
+    /// paths are walked; every subtree entirely inside the range contributes its cached summary.
+    #[allow(dead_code, reason = "the demo keeps no summaries")]
+    pub fn aggregate_range<R: RangeBounds<T>>(&self, range: R) -> A::Value {
+        self.fold_range(&self.root, range.start_bound(), range.end_bound())
+    }
+
+    fn fold_range(&self, root: &RedBlackTree<T, A::Value>, start: Bound<&T>, end: Bound<&T>) -> A::Value {
+        let Some(node) = root else {
+            return self.aggregate.identity();
+        };
+        let node = node.borrow();
+        if let (Bound::Unbounded, Bound::Unbounded) = (start, end) {
+            return node.summary.clone();
         }
-        false
+        let after_start = match start {
+            Bound::Included(start) => self.compare.compare(node.key(), start) != Ordering::Less,
+            Bound::Excluded(start) => self.compare.compare(node.key(), start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        };
+        let before_end = match end {
+            Bound::Included(end) => self.compare.compare(node.key(), end) != Ordering::Greater,
+            Bound::Excluded(end) => self.compare.compare(node.key(), end) == Ordering::Less,
+            Bound::Unbounded => true,
+        };
+        if !after_start {
+            return self.fold_range(&node.right, start, end);
+        }
+        if !before_end {
+            return self.fold_range(&node.left, start, end);
+        }
+        // The range splits at this node: everything left of it only needs the start bound and
+        // everything right of it only the end bound.
+        let left = self.fold_range(&node.left, start, Bound::Unbounded);
+        let right = self.fold_range(&node.right, Bound::Unbounded, end);
+        let own = self.aggregate.lift_n(node.key(), node.count);
+        self.aggregate.combine(&self.aggregate.combine(&left, &own), &right)
     }
 
-    fn draw_tree(&self, file: &mut Dotfile) {
-        if self.root.is_none() {
-            println!("There is nothing to draw")
+    /// Returns the keys within `range` in ascending order, with bounds judged by the tree's comparator.
+    #[allow(dead_code, reason = "query API, unused by the demo")]
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut keys = Vec::new();
+        self.collect_range(&self.root, &range, &mut keys);
+        keys
+    }
+
+    fn collect_range<R: RangeBounds<T>>(&self, root: &RedBlackTree<T, A::Value>, range: &R, keys: &mut Vec<T>)
+    where
+        T: Clone,
+    {
+        if let Some(node) = root {
+            let node = node.borrow();
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => self.compare.compare(node.key(), start) != Ordering::Less,
+                Bound::Excluded(start) => self.compare.compare(node.key(), start) == Ordering::Greater,
+                Bound::Unbounded => true,
+            };
+            let before_end = match range.end_bound() {
+                Bound::Included(end) => self.compare.compare(node.key(), end) != Ordering::Greater,
+                Bound::Excluded(end) => self.compare.compare(node.key(), end) == Ordering::Less,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                self.collect_range(&node.left, range, keys);
+            }
+            if after_start && before_end {
+                keys.push(node.key().clone());
+            }
+            if before_end {
+                self.collect_range(&node.right, range, keys);
+            }
         }
-        TreeNode::draw_node(&self.root.clone(), file, None)
     }
 
-    fn find_node(&self, key: T) -> RedBlackTree<T> {
-        let mut current = self.root.clone();
-        while let Some(node) = current.clone() {
-            match key.cmp(&node.clone().borrow().key) {
-                Ordering::Less => {
-                    current = node.borrow().left.clone();
-                }
-                Ordering::Equal => {
-                    return Some(node)
-                }
-                Ordering::Greater => {
-                    current = node.borrow().right.clone();
-                }
+    /// Unlinks `node` from the tree and rebalances. When the node has two children its in-order
+    /// successor is relinked into its place, so keys are never copied between nodes and any
+    /// outstanding handle to another node still refers to the same key.
+    fn delete_node(&mut self, node: Tree<T, A::Value>) -> Result<(), TreeError> {
+        self.len -= 1;
+        let left = node.borrow().left.clone();
+        let right = node.borrow().right.clone();
+        let mut removed_color = node.borrow().color.clone();
+
+        // `child` takes the place of the node that is physically removed and `child_parent` is
+        // where it ends up; both are needed because a NIL child has no parent link of its own.
+        let (child, child_parent) = match (left, right) {
+            (None, right) => {
+                let parent = TreeNode::parent_of(&node);
+                self.transplant(&node, right.clone());
+                (right, parent)
+            }
+            (left, None) => {
+                let parent = TreeNode::parent_of(&node);
+                self.transplant(&node, left.clone());
+                (left, parent)
             }
+            (Some(left), Some(right)) => {
+                let successor = self.find_mininmum_node(right.clone());
+                removed_color = successor.borrow().color.clone();
+                let successor_right = successor.borrow().right.clone();
+
+                let child_parent = if Rc::ptr_eq(&successor, &right) {
+                    successor.clone()
+                } else {
+                    let successor_parent = TreeNode::parent_of(&successor);
+                    self.transplant(&successor, successor_right.clone());
+                    right.borrow_mut().parent = Some(Rc::downgrade(&successor));
+                    successor.borrow_mut().right = Some(right);
+                    successor_parent.ok_or_else(|| TreeError::Corrupt("successor below the right child has no parent".to_string()))?
+                };
+
+                self.transplant(&node, Some(successor.clone()));
+                left.borrow_mut().parent = Some(Rc::downgrade(&successor));
+                successor.borrow_mut().left = Some(left);
+                let color = node.borrow().color.clone();
+                self.recolor(&successor, color);
+                (successor_right, Some(child_parent))
+            }
+        };
+
+        {
+            let mut removed = node.borrow_mut();
+            removed.parent = None;
+            removed.left = None;
+            removed.right = None;
         }
-        None
+
+        if let Some(child_parent) = &child_parent {
+            self.refresh_to_root(child_parent.clone());
+        }
+        if removed_color == NodeColor::Black {
+            self.fix_delete(child, child_parent)?;
+        }
+        Ok(())
     }
 
-    fn find_min(&self, mut node: Tree<T>) -> Tree<T> {
-        let mut current_node = node.clone();
-        while let Some(left) = current_node.clone().borrow().left.clone() {
-            current_node = left.clone();
+    /// Replaces the subtree rooted at `node` with `replacement` in `node`'s parent (or the root).
+    fn transplant(&mut self, node: &Tree<T, A::Value>, replacement: RedBlackTree<T, A::Value>) {
+        let parent_link = node.borrow().parent.clone();
+        if let Some(replacement_node) = &replacement {
+            replacement_node.borrow_mut().parent = parent_link.clone();
+        }
+        match parent_link.and_then(|p| p.upgrade()) {
+            Some(parent) => {
+                let is_left = parent.borrow().left.as_ref().is_some_and(|child| Rc::ptr_eq(node, child));
+                if is_left {
+                    parent.borrow_mut().left = replacement;
+                } else {
+                    parent.borrow_mut().right = replacement;
+                }
+            }
+            None => self.root = replacement,
         }
-        node
     }
 
-    fn fix_delete(&mut self, mut node: Tree<T>) {
-        while let Some(parent) = node.borrow().parent.clone().and_then(|p| p.upgrade()) {
-            // If the parent is black, there's no violation
-            if parent.borrow().color == NodeColor::Black {
+    /// Restores the black height after a black node was removed. `node` carries the extra black
+    /// and may be NIL, which is why its parent is passed alongside it.
+    fn fix_delete(&mut self, mut node: RedBlackTree<T, A::Value>, mut parent: RedBlackTree<T, A::Value>) -> Result<(), TreeError> {
+        while let Some(parent_node) = parent.clone() {
+            if TreeNode::is_red(&node) {
                 break;
             }
 
-            // Handle red node violations
-            let is_left = Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap());
-            let sibling = if is_left {
-                parent.borrow().right.clone()
-            } else {
-                parent.borrow().left.clone()
+            let is_left = match &node {
+                Some(node) => parent_node.borrow().left.as_ref().is_some_and(|left| Rc::ptr_eq(node, left)),
+                None => parent_node.borrow().left.is_none(),
+            };
+            let sibling_of = |parent_node: &Tree<T, A::Value>| {
+                let parent_ref = parent_node.borrow();
+                let sibling = if is_left { parent_ref.right.clone() } else { parent_ref.left.clone() };
+                sibling.ok_or_else(|| TreeError::Corrupt("a doubly black node has no sibling".to_string()))
             };
 
-            if let Some(sibling_node) = sibling {
-                if sibling_node.borrow().color == NodeColor::Red {
-                    // If sibling is red, rotate to fix the double-red violation
-                    sibling_node.borrow_mut().color = NodeColor::Black;
-                    parent.borrow_mut().color = NodeColor::Red;
-                    if is_left {
-                        self.rotate_left(parent.clone());
-                    } else {
-                        self.rotate_right(parent.clone());
-                    }
+            let mut sibling = sibling_of(&parent_node)?;
+            if sibling.borrow().color == NodeColor::Red {
+                // Case 1: red sibling, rotate it above the parent so the new sibling is black
+                self.recolor(&sibling, NodeColor::Black);
+                self.recolor(&parent_node, NodeColor::Red);
+                if is_left {
+                    self.rotate_left(parent_node.clone())?;
                 } else {
-                    // Handle black sibling cases with more careful fixing
-                    self.handle_black_sibling_case(node.clone(), sibling_node.clone(), parent.clone());
+                    self.rotate_right(parent_node.clone())?;
                 }
+                sibling = sibling_of(&parent_node)?;
             }
-        }
 
-        // Ensure root is black
-        self.root.as_ref().unwrap().borrow_mut().color = NodeColor::Black;
-    }
+            let (near, far) = {
+                let sibling_ref = sibling.borrow();
+                if is_left {
+                    (sibling_ref.left.clone(), sibling_ref.right.clone())
+                } else {
+                    (sibling_ref.right.clone(), sibling_ref.left.clone())
+                }
+            };
 
-    fn handle_black_sibling_case(&mut self, node: Tree<T>, sibling: Tree<T>, parent: Tree<T>) {
-        // If both children of the sibling are black, recolor and propagate the fix upwards
-        let left_black = sibling.borrow().left.as_ref().map_or(true, |left| left.borrow().color == NodeColor::Black);
-        let right_black = sibling.borrow().right.as_ref().map_or(true, |right| right.borrow().color == NodeColor::Black);
+            if !TreeNode::is_red(&near) && !TreeNode::is_red(&far) {
+                // Case 2: black sibling with black children, push the extra black up
+                self.recolor(&sibling, NodeColor::Red);
+                parent = TreeNode::parent_of(&parent_node);
+                node = Some(parent_node);
+                continue;
+            }
 
-        if left_black && right_black {
-            sibling.borrow_mut().color = NodeColor::Red;
-            if parent.borrow().color == NodeColor::Black {
-                self.fix_delete_double_black(node);
+            if !TreeNode::is_red(&far) {
+                // Case 3: only the near nephew is red, rotate it into the far position
+                if let Some(near) = &near {
+                    self.recolor(near, NodeColor::Black);
+                }
+                self.recolor(&sibling, NodeColor::Red);
+                if is_left {
+                    self.rotate_right(sibling.clone())?;
+                } else {
+                    self.rotate_left(sibling.clone())?;
+                }
+                sibling = sibling_of(&parent_node)?;
+            }
+
+            // Case 4: far nephew is red, one rotation at the parent absorbs the extra black
+            let parent_color = parent_node.borrow().color.clone();
+            self.recolor(&sibling, parent_color);
+            self.recolor(&parent_node, NodeColor::Black);
+            let far = if is_left { sibling.borrow().right.clone() } else { sibling.borrow().left.clone() };
+            if let Some(far) = far {
+                self.recolor(&far, NodeColor::Black);
+            }
+            if is_left {
+                self.rotate_left(parent_node)?;
             } else {
-                parent.borrow_mut().color = NodeColor::Black;
+                self.rotate_right(parent_node)?;
             }
-        } else {
-            // Handle cases where at least one child is red, perform rotation and recoloring
-            self.fix_red_sibling_case(node, sibling, parent);
+            node = self.root.clone();
+            break;
+        }
+
+        if let Some(node) = node {
+            self.recolor(&node, NodeColor::Black);
         }
+        Ok(())
     }
 
-    fn fix_red_sibling_case(&mut self, node: Tree<T>, sibling: Tree<T>, parent: Tree<T>) {
-        let is_left = Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap());
+    /// Removes `key` from the tree. Returns `false` if it was not present.
+    fn delete(&mut self, key: T) -> bool {
+        self.remove(&key).is_some()
+    }
 
-        // If the sibling's left child is red, rotate accordingly
-        if is_left && sibling.borrow().right.as_ref().map_or(false, |n| n.borrow().color == NodeColor::Red) {
-            // Right child of sibling is red, perform left rotation on sibling
-            self.rotate_left(sibling.clone());
-        } else if !is_left && sibling.borrow().left.as_ref().map_or(false, |n| n.borrow().color == NodeColor::Red) {
-            // Left child of sibling is red, perform right rotation on sibling
-            self.rotate_right(sibling.clone());
+    fn find_mininmum_node(&self, node: Tree<T, A::Value>) -> Tree<T, A::Value> {
+        let mut current = node.clone();
+        while let Some(left) = current.clone().borrow().left.clone() {
+            current = left.clone()
         }
+        current
+    }
 
-        // Now perform rotations on parent to fix the issue
-        sibling.borrow_mut().color = parent.borrow().color.clone();
-        parent.borrow_mut().color = NodeColor::Black;
+    fn find_maximum_node(&self, node: Tree<T, A::Value>) -> Tree<T, A::Value> {
+        let mut current = node.clone();
+        while let Some(right) = current.clone().borrow().right.clone() {
+            current = right.clone()
+        }
+        current
+    }
+}
 
-        if is_left {
-            // After the rotation, set sibling's right child to black and rotate left on parent
-            sibling.borrow_mut().right.as_ref().unwrap().borrow_mut().color = NodeColor::Black;
-            self.rotate_left(parent.clone());
-        } else {
-            // After the rotation, set sibling's left child to black and rotate right on parent
-            sibling.borrow_mut().left.as_ref().unwrap().borrow_mut().color = NodeColor::Black;
-            self.rotate_right(parent.clone());
+impl<T: std::fmt::Debug, C, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    fn draw_tree(&self, file: &mut Dotfile) {
+        if self.root.is_none() {
+            return;
         }
+        draw_node(self.root.clone(), file, None)
     }
+}
 
+fn main() -> ExitCode {
+    cli::run(std::env::args().skip(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
 
+    use super::*;
 
-    fn delete_node(&mut self, node: Tree<T>) {
-        let mut to_fix = node.clone();
+    /// Ordered by `id` alone, so keys that compare equal can still be told apart by their tag.
+    #[derive(Clone, Debug)]
+    struct Tagged {
+        id: i32,
+        tag: &'static str,
+    }
 
-        if node.borrow().left.is_some() && node.borrow().right.is_some() {
-            // Step 1: Find the in-order successor if the node has two children
-            let successor = self.find_min(node.borrow().right.clone().unwrap());
-            node.borrow_mut().key = successor.borrow().key.clone();  // Replace key
-            to_fix = successor.clone(); // Now delete the successor node
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Self) -> bool {
+            self.id == other.id
         }
+    }
 
-        // Step 2: Find the replacement (either left or right child)
-        let replacement = if to_fix.borrow().left.is_some() {
-            to_fix.borrow().left.clone()
-        } else {
-            to_fix.borrow().right.clone()
-        };
+    impl Eq for Tagged {}
 
-        // Step 3: Handle the replacement
-        if let Some(replacement_node) = replacement.clone() {
-            // Update the parent of the replacement node
-            replacement_node.borrow_mut().parent = to_fix.borrow().parent.clone();
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
 
-            // Update the parent's child reference to point to the replacement
-            if let Some(parent) = to_fix.borrow().parent.clone().and_then(|p| p.upgrade()) {
-                if Rc::ptr_eq(&to_fix, &parent.borrow().left.as_ref().unwrap()) {
-                    parent.borrow_mut().left = replacement.clone();
-                } else {
-                    parent.borrow_mut().right = replacement.clone();
-                }
-            } else {
-                self.root = replacement.clone(); // This is the root node
-            }
-        } else if let Some(parent) = to_fix.borrow().parent.clone().and_then(|p| p.upgrade()) {
-            // No replacement, just remove the reference from the parent
-            if Rc::ptr_eq(&to_fix, &parent.borrow().left.as_ref().unwrap()) {
-                parent.borrow_mut().left = None;
-            } else {
-                parent.borrow_mut().right = None;
-            }
-        } else {
-            // If no parent, the node is the root node
-            self.root = None;
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.id.cmp(&other.id)
         }
+    }
 
-        // Step 4: Fix the red-black properties after deletion
-        if to_fix.borrow().color == NodeColor::Black {
-            if let Some(replacement_node) = replacement {
-                // If the node deleted was black, fix the double-black violation
-                self.fix_delete(replacement_node);
-            } else {
-                // If replacement was null, fix double-black violation for parent
-                self.fix_delete_double_black(to_fix);
-            }
+    impl fmt::Display for Tagged {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}{}", self.id, self.tag)
         }
     }
 
+    fn tag_of(tree: &RedBlackTreeStructure<Tagged>, id: i32) -> Option<&'static str> {
+        tree.find_node(&Tagged { id, tag: "" }).map(|node| node.borrow().key().tag)
+    }
 
-    fn fix_double_red(&mut self, mut node: Tree<T>) {
-        while let Some(parent) = node.clone().borrow().parent.clone().and_then(|p| p.upgrade()) {
-            // If the parent is black, no double-red violation exists
-            if parent.borrow().color == NodeColor::Black {
-                break;
-            }
+    #[test]
+    fn reject_and_replace_resolve_equal_keys() {
+        let mut reject = RedBlackTreeStructure::new();
+        assert_eq!(reject.policy(), DuplicatePolicy::Reject);
+        assert!(reject.insert(Tagged { id: 1, tag: "a" }));
+        assert!(!reject.insert(Tagged { id: 1, tag: "b" }));
+        assert_eq!((tag_of(&reject, 1), reject.len()), (Some("a"), 1));
+
+        let mut replace = RedBlackTreeStructure::with_policy(DuplicatePolicy::Replace);
+        assert!(replace.insert(Tagged { id: 1, tag: "a" }));
+        assert!(!replace.insert(Tagged { id: 1, tag: "b" }));
+        assert_eq!((tag_of(&replace, 1), replace.len()), (Some("b"), 1));
+        assert_eq!(replace.count(&Tagged { id: 1, tag: "" }), 1);
+    }
 
-            if let Some(grandparent) = parent.borrow().parent.clone().and_then(|p| p.upgrade()) {
-                let is_left = Rc::ptr_eq(&parent, &grandparent.borrow().left.as_ref().unwrap());
-                let uncle = if is_left {
-                    grandparent.borrow().right.clone()
-                } else {
-                    grandparent.borrow().left.clone()
-                };
+    #[test]
+    fn count_keeps_one_node_per_key() {
+        let mut tree = RedBlackTreeStructure::with_policy(DuplicatePolicy::Count);
+        for key in [5, 5, 5, 7] {
+            tree.insert(key);
+        }
+        assert_eq!((tree.count(&5), tree.count(&7), tree.count(&6), tree.len()), (3, 1, 0, 2));
+        assert!(tree.remove_one(&5));
+        assert_eq!((tree.count(&5), tree.len()), (2, 2));
+        assert_eq!(tree.remove_all(&5), 2);
+        assert_eq!(tree.remove_all(&5), 0);
+        assert!(!tree.remove_one(&5));
+        assert!(tree.remove_one(&7));
+        assert_eq!(tree.len(), 0);
+        assert!(tree.tree_is_empty());
+    }
 
-                if let Some(uncle_node) = uncle {
-                    if uncle_node.borrow().color == NodeColor::Red {
-                        // Case 1: Uncle is red - recolor parent, uncle, and grandparent
-                        parent.borrow_mut().color = NodeColor::Black;
-                        uncle_node.borrow_mut().color = NodeColor::Black;
-                        grandparent.borrow_mut().color = NodeColor::Red;
-                        drop(node);
-                        node = grandparent; // Move up to grandparent
-                    } else {
-                        // Case 2: Uncle is black, perform rotations
-                        if is_left {
-                            if Rc::ptr_eq(&node, &parent.borrow().right.as_ref().unwrap()) {
-                                // Left-right case
-                                self.rotate_left(parent.clone());
-                                node = parent.clone();
-                            }
-                            // Left-left case
-                            parent.borrow_mut().color = NodeColor::Black;
-                            grandparent.borrow_mut().color = NodeColor::Red;
-                            self.rotate_right(grandparent.clone());
-                        } else {
-                            if Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap()) {
-                                // Right-left case
-                                self.rotate_right(parent.clone());
-                                node = parent.clone();
-                            }
-                            // Right-right case
-                            parent.borrow_mut().color = NodeColor::Black;
-                            grandparent.borrow_mut().color = NodeColor::Red;
-                            self.rotate_left(grandparent.clone());
-                        }
-                        break;
-                    }
-                } else {
-                    // Case 3: No uncle (treat as Case 2 with rotations)
-                    if is_left {
-                        if Rc::ptr_eq(&node, &parent.borrow().right.as_ref().unwrap()) {
-                            self.rotate_left(parent.clone());
-                            node = parent.clone();
-                        }
-                        parent.borrow_mut().color = NodeColor::Black;
-                        grandparent.borrow_mut().color = NodeColor::Red;
-                        self.rotate_right(grandparent.clone());
-                    } else {
-                        if Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap()) {
-                            self.rotate_right(parent.clone());
-                            node = parent.clone();
-                        }
-                        parent.borrow_mut().color = NodeColor::Black;
-                        grandparent.borrow_mut().color = NodeColor::Red;
-                        self.rotate_left(grandparent.clone());
-                    }
-                    break;
-                }
-            } else {
-                break; // No grandparent, stop fixing
-            }
+    #[test]
+    fn comparators_order_keys_and_range_bounds() {
+        let mut tree = RedBlackTreeStructure::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        for key in [4, 9, 1, 7, 3] {
+            tree.insert(key);
+        }
+        assert_eq!(tree.range(..), vec![9, 7, 4, 3, 1]);
+        assert_eq!(tree.range((Bound::Included(8), Bound::Excluded(3))), vec![7, 4]);
+        tree.delete(7);
+        assert_eq!(tree.range(..), vec![9, 4, 3, 1]);
+        assert_eq!(tree.count(&7), 0);
+
+        let by_lowercase = |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase());
+        let mut words = RedBlackTreeStructure::with_comparator_and_policy(by_lowercase, DuplicatePolicy::Count);
+        for word in ["b", "A", "a", "B", "c"] {
+            words.insert(word.to_string());
         }
+        assert_eq!(words.range(..), vec!["A", "b", "c"]);
+        assert_eq!(words.count(&"B".to_string()), 2);
+    }
 
-        // Ensure the root is black
-        self.root.as_ref().unwrap().borrow_mut().color = NodeColor::Black;
+    /// A key with nothing but an order: no `Clone`, `Debug` or `Display`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Opaque(u32);
+
+    #[test]
+    fn core_operations_need_only_an_order() {
+        let mut tree = RedBlackTreeStructure::with_policy(DuplicatePolicy::Count);
+        let inserted: Vec<bool> = [3, 1, 4, 1, 5, 9, 2, 6].into_iter().map(|key| tree.insert(Opaque(key))).collect();
+        assert_eq!(inserted, [true, true, true, false, true, true, true, true]);
+        assert_eq!((tree.len(), tree.count(&Opaque(1))), (7, 2));
+        tree.delete(Opaque(4));
+        assert_eq!((tree.len(), tree.count(&Opaque(4))), (6, 0));
+        assert_eq!(tree.remove_all(&Opaque(1)), 2);
+        assert!(tree.find_node(&Opaque(9)).is_some());
     }
 
+    #[test]
+    fn deleting_a_node_with_two_children_relinks_its_successor() {
+        let mut tree = RedBlackTreeStructure::new();
+        for key in 1..=10 {
+            tree.insert(key);
+        }
+        // 4 is the root and 5 its successor; the node holding 5 must survive the delete.
+        let successor = tree.find_node(&5).expect("5 is in the tree");
+        tree.delete(4);
+        assert_eq!(*successor.borrow().key(), 5);
+        assert!(tree.root.as_ref().is_some_and(|root| Rc::ptr_eq(root, &successor)));
+        assert!(successor.borrow().parent.is_none());
+        for key in (1..=10).filter(|&key| key != 4) {
+            let node = tree.find_node(&key).expect("only 4 was deleted");
+            for child in [&node.borrow().left, &node.borrow().right].into_iter().flatten() {
+                let parent = child.borrow().parent.as_ref().and_then(|parent| parent.upgrade());
+                assert!(parent.is_some_and(|parent| Rc::ptr_eq(&parent, &node)));
+            }
+        }
+        assert!(tree.find_node(&4).is_none());
+    }
 
+    struct Sum;
 
+    impl Aggregate<u64> for Sum {
+        type Value = u64;
 
+        fn identity(&self) -> u64 {
+            0
+        }
 
+        fn lift(&self, key: &u64) -> u64 {
+            *key
+        }
 
-    fn delete(&mut self, key: T) {
-        if let Some(node) = self.find_node(key) {
-            self.delete_node(node)
+        fn combine(&self, left: &u64, right: &u64) -> u64 {
+            left + right
         }
     }
 
-    fn fix_delete_double_black(&mut self, mut node: Tree<T>) {
-        while let Some(parent) = node.clone().borrow().parent.clone().and_then(|p| p.upgrade()) {
-            let is_left = Rc::ptr_eq(&node, &parent.borrow().left.as_ref().unwrap());
-            let sibling = if is_left {
-                parent.borrow().right.clone()
-            } else {
-                parent.borrow().left.clone()
-            };
+    #[test]
+    fn counted_keys_add_up_in_aggregates() {
+        let mut tree = RedBlackTreeStructure::from_parts(NaturalOrder, DuplicatePolicy::Count, Sum);
+        for key in [5, 5, 7] {
+            tree.insert(key);
+        }
+        assert_eq!(tree.aggregate_range(0..10), 17);
+        assert_eq!(tree.aggregate_range(5..=5), 10);
+        tree.remove_one(&5);
+        assert_eq!(tree.aggregate(), 12);
+        for _ in 0..6 {
+            tree.insert(7);
+        }
+        assert_eq!(tree.aggregate_range(6..), 49);
+        assert_eq!(tree.remove_all(&7), 7);
+        assert_eq!(tree.aggregate(), 5);
+    }
 
-            if let Some(sibling_node) = sibling {
-                if sibling_node.borrow().color == NodeColor::Red {
-                    // Case 1: Sibling is red
-                    sibling_node.borrow_mut().color = NodeColor::Black;
-                    parent.borrow_mut().color = NodeColor::Red;
-                    if is_left {
-                        self.rotate_left(parent.clone());
-                    } else {
-                        self.rotate_right(parent.clone());
-                    }
-                } else {
-                    let left_black = sibling_node
-                        .borrow()
-                        .left
-                        .as_ref()
-                        .map_or(true, |left| left.borrow().color == NodeColor::Black);
-                    let right_black = sibling_node
-                        .borrow()
-                        .right
-                        .as_ref()
-                        .map_or(true, |right| right.borrow().color == NodeColor::Black);
-
-                    if left_black && right_black {
-                        // Case 2: Sibling and its children are black
-                        sibling_node.borrow_mut().color = NodeColor::Red;
-                        if parent.borrow().color == NodeColor::Black {
-                            drop(node);
-                            node = parent.clone(); // Continue fixing the double-black up the tree
-                        } else {
-                            parent.borrow_mut().color = NodeColor::Black;
-                            break; // No further fixing needed
-                        }
-                    } else {
-                        // Case 3: At least one of sibling's children is red
-                        if is_left && right_black {
-                            // Left child of sibling is red, and node is left child
-                            sibling_node.borrow_mut().color = NodeColor::Red;
-                            sibling_node.borrow_mut().left.as_ref().unwrap().borrow_mut().color =
-                                NodeColor::Black;
-                            self.rotate_right(sibling_node.clone());
-                        } else if !is_left && left_black {
-                            // Right child of sibling is red, and node is right child
-                            sibling_node.borrow_mut().color = NodeColor::Red;
-                            sibling_node.borrow_mut().right.as_ref().unwrap().borrow_mut().color =
-                                NodeColor::Black;
-                            self.rotate_left(sibling_node.clone());
-                        }
-
-                        sibling_node.borrow_mut().color = parent.borrow().color.clone();
-                        parent.borrow_mut().color = NodeColor::Black;
-
-                        if is_left {
-                            sibling_node
-                                .borrow_mut()
-                                .right
-                                .as_ref()
-                                .unwrap()
-                                .borrow_mut()
-                                .color = NodeColor::Black;
-                            self.rotate_left(parent.clone());
-                        } else {
-                            sibling_node
-                                .borrow_mut()
-                                .left
-                                .as_ref()
-                                .unwrap()
-                                .borrow_mut()
-                                .color = NodeColor::Black;
-                            self.rotate_right(parent.clone());
-                        }
-
-                        break; // Fixing is complete
-                    }
-                }
-            } else {
-                // Case 4: No sibling (shouldn't happen in a valid RB tree)
-                break;
-            }
+    #[test]
+    fn aggregates_fold_over_ranges() {
+        let mut tree = RedBlackTreeStructure::with_aggregate(Sum);
+        for key in 1..=10 {
+            tree.insert(key);
         }
+        assert_eq!(tree.aggregate(), 55);
+        assert_eq!(tree.aggregate_range(3..6), 12);
+        assert_eq!(tree.aggregate_range(..=2), 3);
+    }
 
-        // Finally, make the node black
-        node.borrow_mut().color = NodeColor::Black;
+    #[test]
+    fn try_methods_report_absent_keys() {
+        let mut tree: RedBlackTreeStructure<i32> = (0..8).collect();
+        assert!(matches!(tree.try_remove(&100), Err(TreeError::NotFound)));
+        assert!(matches!(tree.try_remove_one(&100), Err(TreeError::NotFound)));
+        assert!(matches!(tree.try_remove_all(&6), Ok(1)));
+        assert!(matches!(tree.try_remove_all(&6), Err(TreeError::NotFound)));
+        assert!(tree.try_remove_one(&5).is_ok());
+        assert_eq!(tree.try_remove(&4).ok(), Some(4));
+        assert_eq!(tree.len(), 5);
     }
 
-    fn find_mininmum_node(&self, node: Tree<T>) -> Tree<T> {
-        let mut current = node.clone();
-        while let Some(left) = current.clone().borrow().left.clone() {
-            current = left.clone()
+    #[test]
+    fn try_methods_return_audit_failures() {
+        let mut tree: RedBlackTreeStructure<i32> = (0..8).collect();
+        tree.set_audit(true);
+        let leaf = tree.find_node(&0).expect("0 is in the tree");
+        leaf.borrow_mut().parent = None;
+        assert!(matches!(tree.try_insert(100), Err(TreeError::InvariantViolated(_))));
+        assert!(matches!(tree.try_remove(&100), Err(TreeError::InvariantViolated(_))));
+        assert!(matches!(tree.try_remove_one(&5), Err(TreeError::InvariantViolated(_))));
+        assert!(matches!(tree.try_remove_all(&6), Err(TreeError::InvariantViolated(_))));
+        assert!(matches!(tree.try_remove_all(&6), Err(TreeError::NotFound)));
+    }
+
+    #[test]
+    fn drawings_are_written_to_their_file() {
+        let tree: RedBlackTreeStructure<i32> = (1..=5).collect();
+        let path = std::env::temp_dir().join(format!("rbt_test_{}.dot", std::process::id()));
+        let mut file = Dotfile::new(path.to_str().expect("temp dir is valid UTF-8"));
+        tree.draw_tree(&mut file);
+        file.write_file();
+        let written = std::fs::read_to_string(&path).expect("write_file created the file");
+        std::fs::remove_file(&path).expect("the file exists");
+        assert_eq!(written, Dot.render(&file));
+
+        let missing = Dotfile::new("/nonexistent/dir/rbt.dot");
+        assert!(matches!(missing.try_write_file(), Err(TreeError::Io(_))));
+    }
+
+    #[test]
+    fn lift_n_matches_repeated_combines() {
+        for count in 0..20 {
+            assert_eq!(Sum.lift_n(&3, count), 3 * count as u64);
+        }
+    }
+
+    #[test]
+    fn remove_take_and_delete_report_what_they_removed() {
+        let mut tree = RedBlackTreeStructure::new();
+        for key in [3, 1, 2] {
+            tree.insert(key);
+        }
+        assert_eq!(tree.remove(&2), Some(2));
+        assert_eq!(tree.remove(&2), None);
+        assert_eq!(tree.take(&1), Some(1));
+        assert!(tree.delete(3));
+        assert!(!tree.delete(3));
+        assert!(tree.tree_is_empty());
+    }
+
+    #[test]
+    fn removing_under_audit_frees_the_node() {
+        let mut tree: RedBlackTreeStructure<i32> = (0..16).collect();
+        tree.set_audit(true);
+        for key in 0..16 {
+            assert_eq!(tree.try_remove(&key).ok(), Some(key));
         }
-        current
     }
-}
 
-fn main() {
-    let mut rb_tree = RedBlackTreeStructure::new();
-    rb_tree.insert(10);
-    rb_tree.insert(20);
-    rb_tree.insert(30);
-    rb_tree.insert(15);
-    rb_tree.insert(25);
-    rb_tree.insert(5);
-    rb_tree.insert(6);
-    rb_tree.insert(1);
-    rb_tree.insert(43);
-    let count = RedBlackTreeStructure::<u32>::number_of_leaves(&rb_tree.root);
-    let height = RedBlackTreeStructure::height_of_tree(&rb_tree.root);
-    println!("{:#?}", rb_tree.root);
-    println!("Leaf Count:{}", count);
-    println!("Height:{}", height);
-    println!("Tree traversal");
-    RedBlackTreeStructure::in_order_traversal(&rb_tree.root);
-    println!("Is tree Empty:{}", rb_tree.tree_is_empty());
-    let mut dot_file = Dotfile::new("./rbt.dot");
-    rb_tree.draw_tree(&mut dot_file);
-    dot_file.write_file();
-    rb_tree.delete(5);
-    rb_tree.delete(30);
-    rb_tree.delete(10);
-    rb_tree.delete(43);
-    let mut dot_file = Dotfile::new("./rbt_after_delete.dot");
-    rb_tree.draw_tree(&mut dot_file);
-    dot_file.write_file();
-    println!("{:#?}", rb_tree.root);
-    RedBlackTreeStructure::in_order_traversal(&rb_tree.root);
+    #[test]
+    fn removing_a_node_a_leaked_handle_still_points_at() {
+        let mut tree: RedBlackTreeStructure<i32> = (0..16).collect();
+        std::mem::forget(tree.inorder());
+        std::mem::forget(tree.cursor_at(&7));
+        assert_eq!(tree.remove(&0), Some(0));
+        assert_eq!(tree.remove(&7), Some(7));
+        assert_eq!(tree.take_all().len(), 14);
+        assert!(tree.validate().is_ok());
+    }
 }
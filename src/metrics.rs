@@ -0,0 +1,149 @@
+use std::fs;
+
+use crate::bench::workload;
+use crate::error::TreeError;
+use crate::{Aggregate, Compare, RedBlackTreeStructure};
+
+/// One step of a workload replayed by `run_workload`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation<T> {
+    Insert(T),
+    Remove(T),
+}
+
+/// The tree after the first `operations` steps of a workload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsSample {
+    pub operations: usize,
+    pub len: usize,
+    pub height: usize,
+    pub black_height: usize,
+    /// Totals since the tree was created, see `rotations` and `recolors`.
+    pub rotations: u64,
+    pub recolors: u64,
+    /// 2·log2(len + 1), the most a red-black tree of `len` nodes can be tall.
+    pub height_bound: f64,
+}
+
+/// The samples taken by `run_workload`, in the order they were taken.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct MetricsSeries {
+    pub samples: Vec<MetricsSample>,
+}
+
+impl MetricsSeries {
+    /// One line per sample under a header, ready to plot height and black height against
+    /// `height_bound`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("operations,len,height,black_height,rotations,recolors,height_bound\n");
+        for sample in &self.samples {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{:.3}\n",
+                sample.operations, sample.len, sample.height, sample.black_height, sample.rotations, sample.recolors, sample.height_bound
+            ));
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: &str) -> Result<(), TreeError> {
+        fs::write(path, self.to_csv())?;
+        Ok(())
+    }
+}
+
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Applies `operations` in order, sampling the tree before the first one, after every
+    /// `every`-th one and after the last. With `every` at 0 only the first and last samples are
+    /// taken. Each sample walks the whole tree, so a small `every` on a big workload is slow.
+    pub fn run_workload<I: IntoIterator<Item = Operation<T>>>(&mut self, operations: I, every: usize) -> MetricsSeries {
+        let mut series = MetricsSeries { samples: vec![self.sample(0)] };
+        let mut done = 0;
+        for operation in operations {
+            match operation {
+                Operation::Insert(key) => {
+                    self.insert(key);
+                }
+                Operation::Remove(key) => {
+                    self.remove(&key);
+                }
+            }
+            done += 1;
+            if every > 0 && done % every == 0 {
+                series.samples.push(self.sample(done));
+            }
+        }
+        if series.samples.last().is_some_and(|last| last.operations != done) {
+            series.samples.push(self.sample(done));
+        }
+        series
+    }
+
+    fn sample(&self, operations: usize) -> MetricsSample {
+        let stats = self.stats();
+        MetricsSample {
+            operations,
+            len: self.len,
+            height: stats.height,
+            black_height: stats.black_height,
+            rotations: self.rotations,
+            recolors: self.recolors,
+            height_bound: stats.height_bound,
+        }
+    }
+}
+
+/// The `bench` workload as operations: up to `n` distinct pseudo-random keys inserted, then every
+/// other one removed again.
+pub fn insert_then_remove(n: usize, seed: u64) -> Vec<Operation<u64>> {
+    let keys = workload(n, seed);
+    let removed: Vec<Operation<u64>> = keys.iter().copied().step_by(2).map(Operation::Remove).collect();
+    keys.into_iter().map(Operation::Insert).chain(removed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_then_remove, MetricsSample, Operation};
+    use crate::RedBlackTreeStructure;
+
+    fn sampled_at(every: usize, operations: usize) -> Vec<usize> {
+        let mut tree = RedBlackTreeStructure::new();
+        let workload = (0..operations as u64).map(Operation::Insert);
+        tree.run_workload(workload, every).samples.iter().map(|sample| sample.operations).collect()
+    }
+
+    #[test]
+    fn samples_are_taken_at_the_start_every_nth_step_and_the_end() {
+        assert_eq!(sampled_at(0, 10), vec![0, 10]);
+        assert_eq!(sampled_at(5, 10), vec![0, 5, 10]);
+        assert_eq!(sampled_at(4, 10), vec![0, 4, 8, 10]);
+        assert_eq!(sampled_at(4, 0), vec![0]);
+    }
+
+    #[test]
+    fn samples_stay_under_the_height_bound() {
+        let mut tree = RedBlackTreeStructure::new();
+        let series = tree.run_workload(insert_then_remove(2000, 49), 100);
+        assert_eq!(series.samples.len(), 31);
+        for sample in &series.samples {
+            assert!(sample.height as f64 <= sample.height_bound, "{:?}", sample);
+            assert!(sample.black_height <= sample.height);
+        }
+        let last = series.samples.last().expect("the last step is sampled");
+        assert_eq!((last.operations, last.len), (3000, 1000));
+        assert!(last.rotations > 0 && last.recolors > 0);
+    }
+
+    #[test]
+    fn csv_has_one_column_per_field_and_one_row_per_sample() {
+        let mut tree = RedBlackTreeStructure::new();
+        let series = tree.run_workload([Operation::Insert(1), Operation::Insert(2), Operation::Remove(1)], 2);
+        let csv = series.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("operations,len,height,black_height,rotations,recolors,height_bound"));
+        let MetricsSample { operations, len, height, black_height, rotations, recolors, height_bound } = &series.samples[1];
+        let expected = format!("{},{},{},{},{},{},{:.3}", operations, len, height, black_height, rotations, recolors, height_bound);
+        assert_eq!(lines.nth(1), Some(expected.as_str()));
+        assert_eq!(csv.lines().count(), series.samples.len() + 1);
+        assert_eq!(lines.next(), Some("3,1,1,1,0,1,2.000"));
+    }
+}
@@ -0,0 +1,415 @@
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use crate::error::TreeError;
+use crate::validation::check_red_black;
+use crate::{Compare, NaturalOrder, NodeColor, RbNode};
+
+/// An immutable red-black tree. `insert` and `remove` copy only the path from the root to the
+/// changed key and share every other subtree with the version they were called on, so old
+/// versions stay readable and `snapshot` is a reference-count bump.
+///
+/// Nodes have no parent links and no `RefCell`; children are `Arc`s so versions can be handed to
+/// other threads. Balancing follows Kahrs' functional formulation of insertion and deletion.
+/// Keys are ordered by `C`, as in `RedBlackTreeStructure`; every version derived from a tree
+/// keeps its comparator.
+#[derive(Debug)]
+pub struct PersistentRedBlackTree<T, C = NaturalOrder> {
+    root: Link<T>,
+    len: usize,
+    compare: C,
+}
+
+type Link<T> = Option<Arc<PersistentNode<T>>>;
+
+#[derive(Debug)]
+struct PersistentNode<T> {
+    color: NodeColor,
+    key: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> RbNode for &PersistentNode<T> {
+    type Key = T;
+
+    fn color(&self) -> NodeColor {
+        self.color.clone()
+    }
+
+    fn left(&self) -> Option<Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<Self> {
+        self.right.as_deref()
+    }
+
+    fn with_key<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.key)
+    }
+}
+
+// Not derived: that would needlessly require `T: Clone`.
+impl<T, C: Clone> Clone for PersistentRedBlackTree<T, C> {
+    fn clone(&self) -> Self {
+        PersistentRedBlackTree { root: self.root.clone(), len: self.len, compare: self.compare.clone() }
+    }
+}
+
+impl<T, C: Default> Default for PersistentRedBlackTree<T, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<T> PersistentRedBlackTree<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, C> PersistentRedBlackTree<T, C> {
+    /// An empty tree ordered by `compare` instead of `T`'s `Ord`.
+    pub fn with_comparator(compare: C) -> Self {
+        PersistentRedBlackTree { root: None, len: 0, compare }
+    }
+
+    /// Number of keys in this version.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn tree_is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Another handle to this version. Costs O(1) and shares every node.
+    pub fn snapshot(&self) -> Self
+    where
+        C: Clone,
+    {
+        self.clone()
+    }
+
+    pub fn height_of_tree(&self) -> u32 {
+        fn height<T>(link: &Link<T>) -> u32 {
+            link.as_ref().map_or(0, |node| height(&node.left).max(height(&node.right)) + 1)
+        }
+        height(&self.root)
+    }
+}
+
+impl<T, C: Compare<T>> PersistentRedBlackTree<T, C> {
+    pub fn get(&self, key: &T) -> Option<&T> {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match self.compare.compare(key, &node.key) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.key),
+            };
+        }
+        None
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns references to the keys within `range` in ascending order, with bounds judged by
+    /// the tree's comparator.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Vec<&T> {
+        fn collect<'a, T, C: Compare<T>, R: RangeBounds<T>>(link: &'a Link<T>, compare: &C, range: &R, keys: &mut Vec<&'a T>) {
+            if let Some(node) = link {
+                let after_start = match range.start_bound() {
+                    Bound::Included(start) => compare.compare(&node.key, start) != Ordering::Less,
+                    Bound::Excluded(start) => compare.compare(&node.key, start) == Ordering::Greater,
+                    Bound::Unbounded => true,
+                };
+                let before_end = match range.end_bound() {
+                    Bound::Included(end) => compare.compare(&node.key, end) != Ordering::Greater,
+                    Bound::Excluded(end) => compare.compare(&node.key, end) == Ordering::Less,
+                    Bound::Unbounded => true,
+                };
+                if after_start {
+                    collect(&node.left, compare, range, keys);
+                }
+                if after_start && before_end {
+                    keys.push(&node.key);
+                }
+                if before_end {
+                    collect(&node.right, compare, range, keys);
+                }
+            }
+        }
+        let mut keys = Vec::new();
+        collect(&self.root, &self.compare, &range, &mut keys);
+        keys
+    }
+
+    /// Checks the red-black properties of this version and that `len` matches the number of
+    /// nodes. Old versions can be checked as well, after newer ones have been built from them.
+    pub fn validate(&self) -> Result<(), TreeError> {
+        check_red_black(self.root.as_deref(), |a, b| self.compare.compare(a, b))?;
+        fn count<T>(link: &Link<T>) -> usize {
+            link.as_ref().map_or(0, |node| count(&node.left) + count(&node.right) + 1)
+        }
+        let nodes = count(&self.root);
+        if nodes != self.len {
+            return Err(TreeError::InvariantViolated(format!("len is {} but the tree holds {} nodes", self.len, nodes)));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Clone, C: Compare<T> + Clone> PersistentRedBlackTree<T, C> {
+    /// Returns a new version containing `key`. If the key is already present the new version is
+    /// just a snapshot of this one.
+    pub fn insert(&self, key: T) -> Self {
+        if self.contains(&key) {
+            return self.snapshot();
+        }
+        let root = PersistentNode::insert(&self.root, key, &self.compare);
+        PersistentRedBlackTree { root: PersistentNode::blacken(root), len: self.len + 1, compare: self.compare.clone() }
+    }
+
+    /// Returns a new version without `key`.
+    pub fn remove(&self, key: &T) -> Self {
+        if !self.contains(key) {
+            return self.snapshot();
+        }
+        let root = PersistentNode::remove(&self.root, key, &self.compare);
+        PersistentRedBlackTree { root: PersistentNode::blacken(root), len: self.len - 1, compare: self.compare.clone() }
+    }
+}
+
+impl<T: Clone> PersistentNode<T> {
+    fn make(color: NodeColor, left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        Some(Arc::new(PersistentNode { color, key, left, right }))
+    }
+
+    fn red(left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        Self::make(NodeColor::Red, left, key, right)
+    }
+
+    fn black(left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        Self::make(NodeColor::Black, left, key, right)
+    }
+
+    fn red_node(link: &Link<T>) -> Option<&Self> {
+        link.as_deref().filter(|node| node.color == NodeColor::Red)
+    }
+
+    fn black_node(link: &Link<T>) -> Option<&Self> {
+        link.as_deref().filter(|node| node.color == NodeColor::Black)
+    }
+
+    fn blacken(link: Link<T>) -> Link<T> {
+        match Self::red_node(&link) {
+            Some(node) => Self::black(node.left.clone(), node.key.clone(), node.right.clone()),
+            None => link,
+        }
+    }
+
+    /// Turns a black node red, lowering its black height by one for `balance_left`/`balance_right`.
+    fn redden(link: &Link<T>) -> Link<T> {
+        let node = Self::black_node(link).expect("red-black invariant: expected a black node");
+        Self::red(node.left.clone(), node.key.clone(), node.right.clone())
+    }
+
+    /// Rebuilds a node whose children may carry a red-red violation one level down.
+    fn balance(left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        if Self::red_node(&left).is_some() && Self::red_node(&right).is_some() {
+            return Self::red(Self::blacken(left), key, Self::blacken(right));
+        }
+        if let Some(l) = Self::red_node(&left) {
+            if let Some(ll) = Self::red_node(&l.left) {
+                return Self::red(
+                    Self::black(ll.left.clone(), ll.key.clone(), ll.right.clone()),
+                    l.key.clone(),
+                    Self::black(l.right.clone(), key, right),
+                );
+            }
+            if let Some(lr) = Self::red_node(&l.right) {
+                return Self::red(
+                    Self::black(l.left.clone(), l.key.clone(), lr.left.clone()),
+                    lr.key.clone(),
+                    Self::black(lr.right.clone(), key, right),
+                );
+            }
+        }
+        if let Some(r) = Self::red_node(&right) {
+            if let Some(rr) = Self::red_node(&r.right) {
+                return Self::red(
+                    Self::black(left, key, r.left.clone()),
+                    r.key.clone(),
+                    Self::black(rr.left.clone(), rr.key.clone(), rr.right.clone()),
+                );
+            }
+            if let Some(rl) = Self::red_node(&r.left) {
+                return Self::red(
+                    Self::black(left, key, rl.left.clone()),
+                    rl.key.clone(),
+                    Self::black(rl.right.clone(), r.key.clone(), r.right.clone()),
+                );
+            }
+        }
+        Self::black(left, key, right)
+    }
+
+    fn insert<C: Compare<T>>(link: &Link<T>, key: T, compare: &C) -> Link<T> {
+        let Some(node) = link else {
+            return Self::red(None, key, None);
+        };
+        let red = node.color == NodeColor::Red;
+        match compare.compare(&key, &node.key) {
+            Ordering::Less if red => Self::red(Self::insert(&node.left, key, compare), node.key.clone(), node.right.clone()),
+            Ordering::Less => Self::balance(Self::insert(&node.left, key, compare), node.key.clone(), node.right.clone()),
+            Ordering::Greater if red => Self::red(node.left.clone(), node.key.clone(), Self::insert(&node.right, key, compare)),
+            Ordering::Greater => Self::balance(node.left.clone(), node.key.clone(), Self::insert(&node.right, key, compare)),
+            Ordering::Equal => link.clone(),
+        }
+    }
+
+    /// Removes `key`, which must be present. Deleting below a black node lowers that side's black
+    /// height by one, which `balance_left`/`balance_right` repair on the way back up.
+    fn remove<C: Compare<T>>(link: &Link<T>, key: &T, compare: &C) -> Link<T> {
+        let node = link.as_deref()?;
+        match compare.compare(key, &node.key) {
+            Ordering::Less if Self::black_node(&node.left).is_some() => {
+                Self::balance_left(Self::remove(&node.left, key, compare), node.key.clone(), node.right.clone())
+            }
+            Ordering::Less => Self::red(Self::remove(&node.left, key, compare), node.key.clone(), node.right.clone()),
+            Ordering::Greater if Self::black_node(&node.right).is_some() => {
+                Self::balance_right(node.left.clone(), node.key.clone(), Self::remove(&node.right, key, compare))
+            }
+            Ordering::Greater => Self::red(node.left.clone(), node.key.clone(), Self::remove(&node.right, key, compare)),
+            Ordering::Equal => Self::join(&node.left, &node.right),
+        }
+    }
+
+    /// Rebuilds a node whose left subtree is one black level short.
+    fn balance_left(left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        if Self::red_node(&left).is_some() {
+            return Self::red(Self::blacken(left), key, right);
+        }
+        if Self::black_node(&right).is_some() {
+            return Self::balance(left, key, Self::redden(&right));
+        }
+        let r = Self::red_node(&right).expect("red-black invariant: sibling of a short subtree");
+        let rl = Self::black_node(&r.left).expect("red-black invariant: black child below red sibling");
+        Self::red(
+            Self::black(left, key, rl.left.clone()),
+            rl.key.clone(),
+            Self::balance(rl.right.clone(), r.key.clone(), Self::redden(&r.right)),
+        )
+    }
+
+    /// Rebuilds a node whose right subtree is one black level short.
+    fn balance_right(left: Link<T>, key: T, right: Link<T>) -> Link<T> {
+        if Self::red_node(&right).is_some() {
+            return Self::red(left, key, Self::blacken(right));
+        }
+        if Self::black_node(&left).is_some() {
+            return Self::balance(Self::redden(&left), key, right);
+        }
+        let l = Self::red_node(&left).expect("red-black invariant: sibling of a short subtree");
+        let lr = Self::black_node(&l.right).expect("red-black invariant: black child below red sibling");
+        Self::red(
+            Self::balance(Self::redden(&l.left), l.key.clone(), lr.left.clone()),
+            lr.key.clone(),
+            Self::black(lr.right.clone(), key, right),
+        )
+    }
+
+    /// Joins the two subtrees of a removed node, all keys of `left` being below those of `right`.
+    fn join(left: &Link<T>, right: &Link<T>) -> Link<T> {
+        let (Some(l), Some(r)) = (left.as_deref(), right.as_deref()) else {
+            return left.clone().or_else(|| right.clone());
+        };
+        match (l.color.clone(), r.color.clone()) {
+            (NodeColor::Red, NodeColor::Red) => {
+                let middle = Self::join(&l.right, &r.left);
+                match Self::red_node(&middle) {
+                    Some(m) => Self::red(
+                        Self::red(l.left.clone(), l.key.clone(), m.left.clone()),
+                        m.key.clone(),
+                        Self::red(m.right.clone(), r.key.clone(), r.right.clone()),
+                    ),
+                    None => Self::red(l.left.clone(), l.key.clone(), Self::red(middle, r.key.clone(), r.right.clone())),
+                }
+            }
+            (NodeColor::Black, NodeColor::Black) => {
+                let middle = Self::join(&l.right, &r.left);
+                match Self::red_node(&middle) {
+                    Some(m) => Self::red(
+                        Self::black(l.left.clone(), l.key.clone(), m.left.clone()),
+                        m.key.clone(),
+                        Self::black(m.right.clone(), r.key.clone(), r.right.clone()),
+                    ),
+                    None => Self::balance_left(
+                        l.left.clone(),
+                        l.key.clone(),
+                        Self::black(middle, r.key.clone(), r.right.clone()),
+                    ),
+                }
+            }
+            (NodeColor::Black, NodeColor::Red) => {
+                Self::red(Self::join(left, &r.left), r.key.clone(), r.right.clone())
+            }
+            (NodeColor::Red, NodeColor::Black) => {
+                Self::red(l.left.clone(), l.key.clone(), Self::join(&l.right, right))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::ops::Bound;
+
+    use super::PersistentRedBlackTree;
+    use crate::bench::workload;
+
+    #[test]
+    fn old_versions_stay_valid_as_newer_ones_are_built() {
+        let mut versions = vec![(PersistentRedBlackTree::new(), BTreeSet::new())];
+        for step in workload(2000, 31) {
+            let (tree, model) = versions.last().expect("starts with the empty version");
+            let (key, mut model) = (step % 300, model.clone());
+            let next = if (step >> 16) % 3 == 0 {
+                model.remove(&key);
+                tree.remove(&key)
+            } else {
+                model.insert(key);
+                tree.insert(key)
+            };
+            versions.push((next, model));
+        }
+        for (tree, model) in &versions {
+            assert!(tree.validate().is_ok());
+            assert_eq!(tree.len(), model.len());
+            assert_eq!(tree.tree_is_empty(), model.is_empty());
+            assert!(tree.height_of_tree() <= 2 * (tree.len() + 1).ilog2());
+            assert!(tree.range(..).into_iter().eq(model.iter()));
+        }
+    }
+
+    #[test]
+    fn keys_follow_the_comparator() {
+        let descending = |a: &u64, b: &u64| b.cmp(a);
+        let mut tree = PersistentRedBlackTree::with_comparator(descending);
+        for key in [4, 9, 1, 7, 3] {
+            tree = tree.insert(key);
+        }
+        let without_seven = tree.remove(&7);
+        assert!(tree.validate().is_ok() && without_seven.validate().is_ok());
+        assert_eq!(tree.range(..), vec![&9, &7, &4, &3, &1]);
+        assert_eq!(without_seven.range((Bound::Included(8), Bound::Included(3))), vec![&4, &3]);
+        assert_eq!(without_seven.get(&7), None);
+        assert_eq!(tree.get(&7), Some(&7));
+    }
+}
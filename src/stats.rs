@@ -0,0 +1,157 @@
+use std::fmt;
+
+use crate::{Aggregate, Compare, NodeColor, RbNode, RedBlackTreeStructure};
+
+/// Shape of a tree at one point in time, as returned by `stats()`.
+///
+/// Depths count edges from the root, so the root is at depth 0 and `height` (which counts nodes,
+/// like `height_of_tree`) is one more than the deepest depth. A leaf is a node without children.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub height: usize,
+    /// Black nodes on every path from the root down to a NIL, NILs not counted.
+    pub black_height: usize,
+    pub min_leaf_depth: usize,
+    pub avg_leaf_depth: f64,
+    pub max_leaf_depth: usize,
+    /// `depth_histogram[d]` is the number of nodes at depth `d`.
+    pub depth_histogram: Vec<usize>,
+    pub red_nodes: usize,
+    pub black_nodes: usize,
+    /// 2·log2(n + 1), the most a red-black tree of `nodes` nodes can be tall.
+    pub height_bound: f64,
+    /// `height / height_bound`, at most 1 for a valid tree and 0 for an empty one.
+    pub balance_ratio: f64,
+}
+
+impl TreeStats {
+    pub(crate) fn collect<N: RbNode>(root: Option<N>) -> Self {
+        let mut stats = TreeStats {
+            nodes: 0,
+            leaves: 0,
+            height: 0,
+            black_height: 0,
+            min_leaf_depth: 0,
+            avg_leaf_depth: 0.0,
+            max_leaf_depth: 0,
+            depth_histogram: Vec::new(),
+            red_nodes: 0,
+            black_nodes: 0,
+            height_bound: 0.0,
+            balance_ratio: 0.0,
+        };
+        let mut leaf_depth_total = 0;
+        let mut min_leaf_depth = usize::MAX;
+
+        let mut left_spine = root.clone();
+        while let Some(node) = left_spine {
+            stats.black_height += usize::from(node.color() == NodeColor::Black);
+            left_spine = node.left();
+        }
+
+        let mut stack: Vec<(N, usize)> = root.into_iter().map(|root| (root, 0)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            stats.nodes += 1;
+            match node.color() {
+                NodeColor::Red => stats.red_nodes += 1,
+                NodeColor::Black => stats.black_nodes += 1,
+            }
+            if stats.depth_histogram.len() <= depth {
+                stats.depth_histogram.resize(depth + 1, 0);
+            }
+            stats.depth_histogram[depth] += 1;
+
+            let (left, right) = (node.left(), node.right());
+            if left.is_none() && right.is_none() {
+                stats.leaves += 1;
+                leaf_depth_total += depth;
+                min_leaf_depth = min_leaf_depth.min(depth);
+                stats.max_leaf_depth = stats.max_leaf_depth.max(depth);
+            }
+            stack.extend([left, right].into_iter().flatten().map(|child| (child, depth + 1)));
+        }
+
+        if stats.nodes > 0 {
+            stats.height = stats.depth_histogram.len();
+            stats.min_leaf_depth = min_leaf_depth;
+            stats.avg_leaf_depth = leaf_depth_total as f64 / stats.leaves as f64;
+            stats.height_bound = 2.0 * ((stats.nodes + 1) as f64).log2();
+            stats.balance_ratio = stats.height as f64 / stats.height_bound;
+        }
+        stats
+    }
+
+    fn rows(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("nodes".to_string(), self.nodes.to_string()),
+            ("leaves".to_string(), self.leaves.to_string()),
+            ("height".to_string(), self.height.to_string()),
+            ("black_height".to_string(), self.black_height.to_string()),
+            ("min_leaf_depth".to_string(), self.min_leaf_depth.to_string()),
+            ("avg_leaf_depth".to_string(), format!("{:.3}", self.avg_leaf_depth)),
+            ("max_leaf_depth".to_string(), self.max_leaf_depth.to_string()),
+            ("red_nodes".to_string(), self.red_nodes.to_string()),
+            ("black_nodes".to_string(), self.black_nodes.to_string()),
+            ("height_bound".to_string(), format!("{:.3}", self.height_bound)),
+            ("balance_ratio".to_string(), format!("{:.3}", self.balance_ratio)),
+        ];
+        for (depth, count) in self.depth_histogram.iter().enumerate() {
+            rows.push((format!("nodes_at_depth_{}", depth), count.to_string()));
+        }
+        rows
+    }
+
+    /// The statistics as a `metric,value` CSV with a header line.
+    #[allow(dead_code, reason = "export format, unused by the demo")]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("metric,value\n");
+        for (metric, value) in self.rows() {
+            csv.push_str(&format!("{},{}\n", metric, value));
+        }
+        csv
+    }
+}
+
+/// Prints the statistics as an aligned two-column table.
+impl fmt::Display for TreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (metric, value) in self.rows() {
+            writeln!(f, "{:<20}{:>12}", metric, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    pub fn stats(&self) -> TreeStats {
+        TreeStats::collect(self.root.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RedBlackTreeStructure;
+
+    #[test]
+    fn stats_of_a_perfect_tree() {
+        let tree: RedBlackTreeStructure<i32> = (1..=7).collect();
+        let stats = tree.stats();
+        assert_eq!((stats.nodes, stats.leaves, stats.height, stats.black_height), (7, 4, 3, 2));
+        assert_eq!(stats.depth_histogram, vec![1, 2, 4]);
+        assert_eq!((stats.red_nodes, stats.black_nodes), (4, 3));
+        assert_eq!((stats.min_leaf_depth, stats.max_leaf_depth), (2, 2));
+        assert_eq!(stats.height_bound, 6.0);
+        let csv = stats.to_csv();
+        assert!(csv.starts_with("metric,value\nnodes,7\n"));
+        assert!(csv.ends_with("nodes_at_depth_2,4\n"));
+        assert_eq!(csv.lines().count(), stats.to_string().lines().count() + 1);
+    }
+
+    #[test]
+    fn stats_of_an_empty_tree() {
+        let stats = RedBlackTreeStructure::<i32>::new().stats();
+        assert_eq!((stats.nodes, stats.height, stats.balance_ratio), (0, 0, 0.0));
+    }
+}
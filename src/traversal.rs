@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use crate::{Aggregate, Compare, NodeColor, RbNode, RedBlackTree, RedBlackTreeStructure, Tree};
+
+/// Callbacks for `RedBlackTreeStructure::visit`. `enter` runs before a node's subtrees are walked
+/// and `leave` after both of them, so together they bracket each subtree. Depth 0 is the root.
+pub trait Visitor<T> {
+    fn enter(&mut self, _key: &T, _color: NodeColor, _depth: usize) {}
+    fn leave(&mut self, _key: &T, _color: NodeColor, _depth: usize) {}
+}
+
+// The traversals below keep explicit stacks or queues instead of recursing, so a degenerate tree
+// costs heap rather than call stack. They yield clones of the keys, like `range`.
+
+/// Keys in ascending order.
+pub struct Inorder<'a, T, S> {
+    stack: Vec<Tree<T, S>>,
+    tree: PhantomData<&'a ()>,
+}
+
+/// Keys with each node before its subtrees, left before right.
+pub struct Preorder<'a, T, S> {
+    stack: Vec<Tree<T, S>>,
+    tree: PhantomData<&'a ()>,
+}
+
+/// Keys with each node after its subtrees, left before right.
+pub struct Postorder<'a, T, S> {
+    /// `true` once the node's children have been pushed, i.e. it is next to be yielded.
+    stack: Vec<(Tree<T, S>, bool)>,
+    tree: PhantomData<&'a ()>,
+}
+
+/// `(key, depth)` pairs level by level from the root, left to right within a level.
+pub struct LevelOrder<'a, T, S> {
+    queue: VecDeque<(Tree<T, S>, usize)>,
+    tree: PhantomData<&'a ()>,
+}
+
+impl<T, S> Inorder<'_, T, S> {
+    fn push_left_spine(&mut self, mut node: RedBlackTree<T, S>) {
+        while let Some(current) = node {
+            node = current.left();
+            self.stack.push(current);
+        }
+    }
+
+    /// The next node in key order, for callers that want to look at the key without cloning it.
+    pub(crate) fn next_node(&mut self) -> RedBlackTree<T, S> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(node.right());
+        Some(node)
+    }
+}
+
+impl<T: Clone, S> Iterator for Inorder<'_, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.next_node().map(|node| node.with_key(T::clone))
+    }
+}
+
+impl<T: Clone, S> Iterator for Preorder<'_, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.right());
+        self.stack.extend(node.left());
+        Some(node.with_key(T::clone))
+    }
+}
+
+impl<T: Clone, S> Iterator for Postorder<'_, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let (node, children_pushed) = self.stack.pop()?;
+            if children_pushed {
+                return Some(node.with_key(T::clone));
+            }
+            let (left, right) = (node.left(), node.right());
+            self.stack.push((node, true));
+            self.stack.extend(right.map(|right| (right, false)));
+            self.stack.extend(left.map(|left| (left, false)));
+        }
+    }
+}
+
+impl<T: Clone, S> Iterator for LevelOrder<'_, T, S> {
+    type Item = (T, usize);
+
+    fn next(&mut self) -> Option<(T, usize)> {
+        let (node, depth) = self.queue.pop_front()?;
+        self.queue.extend([node.left(), node.right()].into_iter().flatten().map(|child| (child, depth + 1)));
+        Some((node.with_key(T::clone), depth))
+    }
+}
+
+#[allow(dead_code, reason = "the demo prints through in_order_traversal")]
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    pub fn inorder(&self) -> Inorder<'_, T, A::Value> {
+        let mut iter = Inorder { stack: Vec::new(), tree: PhantomData };
+        iter.push_left_spine(self.root.clone());
+        iter
+    }
+
+    pub fn preorder(&self) -> Preorder<'_, T, A::Value> {
+        Preorder { stack: self.root.clone().into_iter().collect(), tree: PhantomData }
+    }
+
+    pub fn postorder(&self) -> Postorder<'_, T, A::Value> {
+        Postorder { stack: self.root.clone().map(|root| (root, false)).into_iter().collect(), tree: PhantomData }
+    }
+
+    pub fn level_order(&self) -> LevelOrder<'_, T, A::Value> {
+        LevelOrder { queue: self.root.clone().map(|root| (root, 0)).into_iter().collect(), tree: PhantomData }
+    }
+
+    /// Walks the tree depth-first, left before right, calling `visitor.enter` on the way down to
+    /// each node and `visitor.leave` once both its subtrees are done.
+    pub fn visit<V: Visitor<T>>(&self, visitor: &mut V) {
+        let mut stack: Vec<(Tree<T, A::Value>, usize, bool)> =
+            self.root.clone().map(|root| (root, 0, false)).into_iter().collect();
+        while let Some((node, depth, entered)) = stack.pop() {
+            let color = node.borrow().color.clone();
+            if entered {
+                visitor.leave(node.borrow().key(), color, depth);
+                continue;
+            }
+            visitor.enter(node.borrow().key(), color, depth);
+            let (left, right) = (node.borrow().left.clone(), node.borrow().right.clone());
+            stack.push((node, depth, true));
+            stack.extend(right.map(|right| (right, depth + 1, false)));
+            stack.extend(left.map(|left| (left, depth + 1, false)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Visitor;
+    use crate::{NodeColor, RedBlackTreeStructure};
+
+    /// Collected into a perfectly balanced tree: 4(2(1, 3), 6(5, 7)).
+    fn ascending() -> RedBlackTreeStructure<i32> {
+        (1..=7).collect()
+    }
+
+    #[test]
+    fn each_order_visits_every_key_once() {
+        let tree = ascending();
+        assert_eq!(tree.inorder().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(tree.preorder().collect::<Vec<_>>(), vec![4, 2, 1, 3, 6, 5, 7]);
+        assert_eq!(tree.postorder().collect::<Vec<_>>(), vec![1, 3, 2, 5, 7, 6, 4]);
+        assert_eq!(tree.level_order().collect::<Vec<_>>(), vec![(4, 0), (2, 1), (6, 1), (1, 2), (3, 2), (5, 2), (7, 2)]);
+    }
+
+    #[test]
+    fn visitor_brackets_each_subtree() {
+        struct Trace(Vec<String>);
+        impl Visitor<i32> for Trace {
+            fn enter(&mut self, key: &i32, _color: NodeColor, depth: usize) {
+                self.0.push(format!("+{}@{}", key, depth));
+            }
+            fn leave(&mut self, key: &i32, _color: NodeColor, _depth: usize) {
+                self.0.push(format!("-{}", key));
+            }
+        }
+        let mut trace = Trace(Vec::new());
+        [2, 1, 3].into_iter().collect::<RedBlackTreeStructure<i32>>().visit(&mut trace);
+        assert_eq!(trace.0, vec!["+2@0", "+1@1", "-1", "+3@1", "-3", "-2"]);
+    }
+
+    #[test]
+    fn a_leaked_iterator_does_not_pin_its_nodes() {
+        let mut tree = ascending();
+        let mut iter = tree.preorder();
+        iter.next();
+        std::mem::forget(iter);
+        for key in 1..=7 {
+            assert_eq!(tree.remove(&key), Some(key));
+        }
+        assert_eq!(tree.len(), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{Aggregate, Compare, Dotfile, NodeColor, RbNode, RedBlackTreeStructure};
+
+/// A node of the 2-3-4 tree (a B-tree of order 4) equivalent to a red-black tree: each black
+/// node together with its red children. Holds one to three keys in order, and either no children
+/// or one more child than keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TwoThreeFourNode<T> {
+    pub keys: Vec<T>,
+    pub children: Vec<TwoThreeFourNode<T>>,
+}
+
+impl<T> TwoThreeFourNode<T> {
+    /// Merges every red node into its black parent. The subtrees hanging below the merged nodes
+    /// all have the same black height, so either all of them are NIL or none is.
+    fn from_red_black<N: RbNode<Key = T>>(node: N) -> Self
+    where
+        T: Clone,
+    {
+        let mut keys = Vec::with_capacity(3);
+        let mut subtrees = Vec::with_capacity(4);
+        let mut absorb = |child: Option<N>, keys: &mut Vec<T>| match child {
+            Some(red) if red.color() == NodeColor::Red => {
+                subtrees.push(red.left());
+                keys.push(red.with_key(T::clone));
+                subtrees.push(red.right());
+            }
+            black => subtrees.push(black),
+        };
+        absorb(node.left(), &mut keys);
+        keys.push(node.with_key(T::clone));
+        absorb(node.right(), &mut keys);
+
+        let children = subtrees.into_iter().flatten().map(Self::from_red_black).collect();
+        TwoThreeFourNode { keys, children }
+    }
+
+    /// Number of nodes on the path from this node down to a leaf, this one included.
+    #[allow(dead_code, reason = "only the tests compare it with the black height")]
+    pub fn height(&self) -> usize {
+        self.children.first().map_or(0, Self::height) + 1
+    }
+
+    fn draw(&self, file: &mut Dotfile) -> usize
+    where
+        T: std::fmt::Debug,
+    {
+        let fields: Vec<String> = self.keys.iter().map(|key| format!("{:?}", key)).collect();
+        let node_idx = file.add_record_node(&fields);
+        for child in &self.children {
+            let child_idx = child.draw(file);
+            file.add_edge(node_idx, child_idx);
+        }
+        node_idx
+    }
+}
+
+impl<T: Clone, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// The equivalent 2-3-4 tree, `None` if this tree is empty.
+    pub fn to_two_three_four(&self) -> Option<TwoThreeFourNode<T>> {
+        self.root.clone().map(TwoThreeFourNode::from_red_black)
+    }
+}
+
+impl<T: Clone + std::fmt::Debug, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Draws the equivalent 2-3-4 tree, one record-shaped node per 2-, 3- or 4-node. Draws
+    /// nothing for an empty tree.
+    pub fn draw_two_three_four(&self, file: &mut Dotfile) {
+        if let Some(root) = self.to_two_three_four() {
+            root.draw(file);
+        }
+    }
+
+    /// Draws the red-black tree and its 2-3-4 equivalent next to each other, each in its own
+    /// labelled box.
+    #[allow(dead_code, reason = "the demo draws the red-black tree on its own")]
+    pub fn draw_side_by_side(&self, file: &mut Dotfile) {
+        file.begin_cluster("red-black");
+        self.draw_tree(file);
+        file.begin_cluster("2-3-4");
+        self.draw_two_three_four(file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TwoThreeFourNode;
+    use crate::{Dotfile, RedBlackTreeStructure};
+
+    fn node(keys: Vec<i32>, children: Vec<TwoThreeFourNode<i32>>) -> TwoThreeFourNode<i32> {
+        TwoThreeFourNode { keys, children }
+    }
+
+    #[test]
+    fn red_nodes_merge_into_their_black_parents() {
+        let mut tree = RedBlackTreeStructure::new();
+        for key in 1..=6 {
+            tree.insert(key);
+        }
+        // 2(1, 4(3, 5(_, 6))) with 4 and 6 red.
+        let expected = node(vec![2, 4], vec![node(vec![1], vec![]), node(vec![3], vec![]), node(vec![5, 6], vec![])]);
+        let root = tree.to_two_three_four().expect("the tree is not empty");
+        assert_eq!(root, expected);
+        assert_eq!(root.height(), tree.stats().black_height);
+        assert_eq!(RedBlackTreeStructure::<i32>::new().to_two_three_four(), None);
+    }
+
+    #[test]
+    fn side_by_side_draws_both_views_in_their_own_cluster() {
+        let tree: RedBlackTreeStructure<i32> = (1..=6).collect();
+        let mut file = Dotfile::new("unused.dot");
+        tree.draw_side_by_side(&mut file);
+        assert_eq!(file.clusters, vec!["red-black".to_string(), "2-3-4".to_string()]);
+        let records: Vec<String> = file.nodes.iter().filter(|node| node.shape.as_deref() == Some("record")).map(|node| node.fields.join(" ")).collect();
+        assert_eq!(records.len(), tree.to_two_three_four().map_or(0, |root| count(&root)));
+        assert!(records.iter().all(|record| !record.is_empty()));
+        assert!(file.nodes.iter().filter(|node| node.cluster == Some(1)).all(|node| node.shape.as_deref() == Some("record")));
+
+        let mut empty = Dotfile::new("unused.dot");
+        RedBlackTreeStructure::<i32>::new().draw_two_three_four(&mut empty);
+        RedBlackTreeStructure::<i32>::new().draw_side_by_side(&mut empty);
+        assert!(empty.nodes.is_empty());
+    }
+
+    fn count(node: &TwoThreeFourNode<i32>) -> usize {
+        node.children.iter().map(count).sum::<usize>() + 1
+    }
+}
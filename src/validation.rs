@@ -0,0 +1,80 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::error::TreeError;
+use crate::{Aggregate, Compare, NodeColor, RbNode, RedBlackTree, RedBlackTreeStructure};
+
+/// Checks the properties every red-black variant shares: a black root, no red node with a red
+/// child, the same number of black nodes on every path down to a NIL, and keys strictly
+/// increasing in order under `compare`. Returns the black height, NILs not counted.
+pub fn check_red_black<N, F>(root: Option<N>, compare: F) -> Result<usize, TreeError>
+where
+    N: RbNode,
+    F: Fn(&N::Key, &N::Key) -> Ordering,
+{
+    if root.as_ref().is_some_and(|root| root.color() == NodeColor::Red) {
+        return Err(TreeError::InvariantViolated("root is red".to_string()));
+    }
+    let mut previous = None;
+    check_subtree(root, &compare, &mut previous)
+}
+
+fn check_subtree<N, F>(node: Option<N>, compare: &F, previous: &mut Option<N>) -> Result<usize, TreeError>
+where
+    N: RbNode,
+    F: Fn(&N::Key, &N::Key) -> Ordering,
+{
+    let Some(node) = node else {
+        return Ok(0);
+    };
+    let red = node.color() == NodeColor::Red;
+    let (left, right) = (node.left(), node.right());
+    if red && [&left, &right].into_iter().flatten().any(|child| child.color() == NodeColor::Red) {
+        return Err(TreeError::InvariantViolated("red node has a red child".to_string()));
+    }
+
+    let left_height = check_subtree(left, compare, previous)?;
+    if let Some(previous) = previous.as_ref() {
+        if previous.with_key(|a| node.with_key(|b| compare(a, b))) != Ordering::Less {
+            return Err(TreeError::InvariantViolated("keys are not strictly increasing in order".to_string()));
+        }
+    }
+    *previous = Some(node);
+    let right_height = check_subtree(right, compare, previous)?;
+    if left_height != right_height {
+        return Err(TreeError::InvariantViolated(format!("black heights differ below a node: {} on the left, {} on the right", left_height, right_height)));
+    }
+    Ok(left_height + usize::from(!red))
+}
+
+impl<T, C: Compare<T>, A: Aggregate<T>> RedBlackTreeStructure<T, C, A> {
+    /// Checks the red-black properties, that every child's parent link points back at its parent
+    /// and that `len` matches the number of nodes. The first violation found is reported as
+    /// `TreeError::InvariantViolated`.
+    pub fn validate(&self) -> Result<(), TreeError> {
+        check_red_black(self.root.clone(), |a, b| self.compare.compare(a, b))?;
+        if self.root.as_ref().is_some_and(|root| root.borrow().parent.is_some()) {
+            return Err(TreeError::InvariantViolated("root has a parent link".to_string()));
+        }
+        let nodes = Self::check_parent_links(&self.root)?;
+        if nodes != self.len {
+            return Err(TreeError::InvariantViolated(format!("len is {} but the tree holds {} nodes", self.len, nodes)));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of nodes below and including `node`.
+    fn check_parent_links(node: &RedBlackTree<T, A::Value>) -> Result<usize, TreeError> {
+        let Some(node) = node else {
+            return Ok(0);
+        };
+        let (left, right) = (node.borrow().left.clone(), node.borrow().right.clone());
+        for child in [&left, &right].into_iter().flatten() {
+            let parent = child.borrow().parent.as_ref().and_then(|parent| parent.upgrade());
+            if !parent.is_some_and(|parent| Rc::ptr_eq(&parent, node)) {
+                return Err(TreeError::InvariantViolated("child's parent link does not point at its parent".to_string()));
+            }
+        }
+        Ok(Self::check_parent_links(&left)? + Self::check_parent_links(&right)? + 1)
+    }
+}